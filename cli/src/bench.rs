@@ -0,0 +1,48 @@
+use std::{path::PathBuf, time::Instant};
+
+use anyhow::Result;
+use clap::ArgMatches;
+use interpreter::C8;
+
+use crate::INSTRUCTIONS_PER_FRAME;
+
+/// Run a program headlessly as fast as possible and report the throughput.
+///
+/// Timers are counted down once every `INSTRUCTIONS_PER_FRAME` instructions rather than by the
+/// wall clock so that runs are reproducible. The run stops early if the program halts, and fails
+/// on an instruction the interpreter can't execute.
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let file: &PathBuf = matches.get_one("FILE").expect("FILE is required");
+    let instructions: u64 = *matches
+        .get_one("instructions")
+        .expect("instructions has a default");
+
+    let mut c8 = C8::new();
+    c8.load_program(file)?;
+    c8.set_profiling(matches.get_flag("by-opcode"));
+
+    let start = Instant::now();
+    let mut ran = 0;
+    while ran < instructions && !c8.is_halted() {
+        c8.try_step()?;
+        ran += 1;
+        if ran % INSTRUCTIONS_PER_FRAME as u64 == 0 {
+            c8.decrement_timers();
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{ran} instructions in {:.3}s ({:.0} instructions/sec)",
+        elapsed.as_secs_f64(),
+        ran as f64 / elapsed.as_secs_f64()
+    );
+    for (family, time, count) in c8.profile_report() {
+        println!(
+            "{family:X}___: {count} instructions in {:.3}s",
+            time.as_secs_f64()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, Result};
+use gilrs::{Button, Gilrs};
+
+/// Chip-8 key for each button unless the profile says otherwise. Most games steer with 2/4/6/8
+/// and act with 5.
+const DEFAULT_MAPPING: [(Button, usize); 10] = [
+    (Button::DPadUp, 0x2),
+    (Button::DPadLeft, 0x4),
+    (Button::DPadRight, 0x6),
+    (Button::DPadDown, 0x8),
+    (Button::South, 0x5),
+    (Button::East, 0x0),
+    (Button::West, 0xa),
+    (Button::North, 0xb),
+    (Button::Select, 0xe),
+    (Button::Start, 0xf),
+];
+
+/// Buttons that can be named in a profile
+const BUTTONS: [Button; 19] = [
+    Button::South,
+    Button::East,
+    Button::North,
+    Button::West,
+    Button::C,
+    Button::Z,
+    Button::LeftTrigger,
+    Button::LeftTrigger2,
+    Button::RightTrigger,
+    Button::RightTrigger2,
+    Button::Select,
+    Button::Start,
+    Button::Mode,
+    Button::LeftThumb,
+    Button::RightThumb,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+
+/// Connected game controllers, read as a Chip-8 keypad
+pub struct Gamepad {
+    gilrs: Gilrs,
+    mapping: Vec<(Button, usize)>,
+}
+
+impl Gamepad {
+    /// Open the controllers, with `overrides` mapping button names such as `South` or `DPadUp`
+    /// to Chip-8 keys in place of the defaults
+    pub fn new(overrides: &HashMap<String, usize>) -> Result<Self> {
+        let mut mapping: HashMap<Button, usize> = DEFAULT_MAPPING.into_iter().collect();
+        for (name, key) in overrides {
+            let button = BUTTONS
+                .into_iter()
+                .find(|b| format!("{b:?}") == *name)
+                .ok_or_else(|| anyhow!("Unknown gamepad button `{name}`"))?;
+            if *key > 0xf {
+                bail!("Gamepad button `{name}` is mapped to {key:#x}, which isn't a key");
+            }
+            mapping.insert(button, *key);
+        }
+
+        let gilrs = Gilrs::new().map_err(|e| anyhow!("Unable to open gamepads: {e}"))?;
+        Ok(Self {
+            gilrs,
+            mapping: mapping.into_iter().collect(),
+        })
+    }
+
+    /// Chip-8 keypad state for the buttons held down on any controller
+    pub fn keypad_state(&mut self) -> [bool; 16] {
+        // the button state only updates as events are drained
+        while self.gilrs.next_event().is_some() {}
+
+        let mut state = [false; 16];
+        for (_, pad) in self.gilrs.gamepads() {
+            for (button, key) in &self.mapping {
+                if pad.is_pressed(*button) {
+                    state[*key] = true;
+                }
+            }
+        }
+        state
+    }
+}
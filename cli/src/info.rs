@@ -0,0 +1,22 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use interpreter::{opcode_histogram, Platform, PROGRAM_START};
+
+/// Print a summary of a program without running it
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let file: &PathBuf = matches.get_one("FILE").expect("FILE is required");
+    let program = fs::read(file).with_context(|| format!("Unable to read {}", file.display()))?;
+
+    println!("size: {}", program.len());
+    println!("entry: {PROGRAM_START:#05x}");
+    println!("platform: {}", Platform::detect(&program));
+    for (family, count) in opcode_histogram(&program).iter().enumerate() {
+        if *count > 0 {
+            println!("opcodes.{family:X}: {count}");
+        }
+    }
+
+    Ok(())
+}
@@ -1,10 +1,28 @@
-use std::{path::PathBuf, time::Duration};
+mod bench;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod info;
+mod profile;
+mod replay;
+mod slots;
+mod vdiff;
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
-use clap::{arg, command, value_parser};
-use interpreter::{C8, HEIGHT, WIDTH};
+use clap::{arg, command, value_parser, ArgMatches, Command};
+use interpreter::{BeepConfig, PixelFormat, Platform, Rotation, Waveform, C8};
 
-use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
+use minifb::{Key, ScaleMode, Window, WindowOptions};
+use profile::Profile;
 
 fn keys_to_key_codes(keys: &[Key]) -> Vec<usize> {
     keys.iter()
@@ -33,25 +51,315 @@ fn keys_to_key_codes(keys: &[Key]) -> Vec<usize> {
         .collect()
 }
 
+/// Chip-8 keypad state for the keys currently held down.
+///
+/// Input is sampled once per frame and compared against the previous frame's state rather than
+/// relying on the window's press/release events, so each change between frames is reported to
+/// the interpreter exactly once. A key pressed and released again between two frames isn't
+/// seen at all.
+fn keypad_state(keys: &[Key]) -> [bool; 16] {
+    let mut state = [false; 16];
+    for k in keys_to_key_codes(keys) {
+        state[k] = true;
+    }
+    state
+}
+
+/// Parse an RGB color written as hex, optionally prefixed with `#` or `0x`
+fn parse_color(s: &str) -> Result<u32, String> {
+    let hex = s
+        .strip_prefix('#')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|c| *c <= 0xff_ff_ff)
+        .ok_or_else(|| format!("`{s}` is not an RGB color"))
+}
+
+/// Window title, which briefly shows save slot messages
+const TITLE: &str = "C8";
+
+/// Window title while paused because the window isn't focused
+const PAUSED_TITLE: &str = "C8 - Paused";
+
+/// How long save slot messages stay in the title
+const SLOT_MESSAGE_TIME: Duration = Duration::from_secs(2);
+
+/// Instructions executed per 60Hz frame
+const INSTRUCTIONS_PER_FRAME: usize = 10;
+
+/// Most instructions to run looking for something to display before opening the window
+const STARTUP_INSTRUCTION_CAP: usize = 100_000;
+
+/// Where Ctrl-C saves the machine state, in the working directory
+const INTERRUPT_STATE_FILE: &str = "c8-interrupted.json";
+
+/// Where Ctrl-C saves a register dump, in the working directory
+const INTERRUPT_DUMP_FILE: &str = "c8-interrupted.txt";
+
+/// Save the machine state and a readable register dump after Ctrl-C
+fn save_interrupted(c8: &C8) -> Result<()> {
+    fs::write(INTERRUPT_STATE_FILE, c8.to_json())
+        .with_context(|| format!("Unable to write {INTERRUPT_STATE_FILE}"))?;
+    fs::write(INTERRUPT_DUMP_FILE, format!("{c8:#?}\n"))
+        .with_context(|| format!("Unable to write {INTERRUPT_DUMP_FILE}"))?;
+    println!("Interrupted, saved the state to {INTERRUPT_STATE_FILE} and {INTERRUPT_DUMP_FILE}.");
+    Ok(())
+}
+
+/// Run `instructions` instructions without waiting on the wall clock, counting the timers down
+/// once every `ipf` instructions as if whole frames had passed. Stops early on Ctrl-C.
+fn skip_boot(c8: &mut C8, instructions: u64, ipf: usize, interrupted: &AtomicBool) -> Result<()> {
+    for n in 1..=instructions {
+        if c8.is_halted() || interrupted.load(Ordering::Relaxed) {
+            break;
+        }
+        c8.try_step()?;
+        if n % ipf.max(1) as u64 == 0 {
+            c8.decrement_timers();
+        }
+    }
+    Ok(())
+}
+
 fn main() -> Result<()> {
     env_logger::init();
 
     let matches = command!()
+        .after_help(format!(
+            "F1-F4 save the machine state to slots 1-4, kept next to the program, and \
+             Shift+F1-F4 load it back. Emulation pauses while the window isn't focused.\n\n\
+             Ctrl-C saves the machine state to {INTERRUPT_STATE_FILE} and a register dump to \
+             {INTERRUPT_DUMP_FILE} in the working directory before exiting."
+        ))
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .arg(arg!(<FILE> "Chip-8 program to execute.").value_parser(value_parser!(PathBuf)))
+        .arg(arg!(--profile <PROFILE> "Quirk profile: a TOML file, `cosmac`, `schip` or `xochip`."))
+        .arg(
+            arg!(--"auto-quirks" "Pick a built-in profile from the instructions the program uses.")
+                .conflicts_with("profile"),
+        )
+        .arg(arg!(--color0 <RGB> "Background color, e.g. 000000.").value_parser(parse_color))
+        .arg(
+            arg!(--color1 <RGB> "Color of pixels lit in the first plane.")
+                .value_parser(parse_color),
+        )
+        .arg(
+            arg!(--color2 <RGB> "Color of pixels lit in the second plane.")
+                .value_parser(parse_color),
+        )
+        .arg(arg!(--color3 <RGB> "Color of pixels lit in both planes.").value_parser(parse_color))
+        .arg(arg!(--fade "Fade pixels out over a few frames to reduce flicker."))
+        .arg(arg!(--blend "Blend each frame with the previous one to reduce flicker."))
+        .arg(
+            arg!(--platform <PLATFORM> "Stop on instructions the platform doesn't support.")
+                .value_parser(["chip8", "schip", "xochip"]),
+        )
+        .arg(
+            arg!(--"draw-limit" <N> "Show at most N sprite draws per frame to reduce flicker.")
+                .value_parser(value_parser!(u64).range(1..))
+                .long_help(
+                    "Show at most N sprite draws per frame, deferring the rest to the next \
+                     frame to reduce flicker. This is a viewing comfort option rather than \
+                     anything the original hardware did.",
+                ),
+        )
+        .arg(
+            arg!(--rotate <DEGREES> "Rotate the picture clockwise.")
+                .value_parser(["90", "180", "270"]),
+        )
+        .arg(
+            arg!(--"beep-freq" <HZ> "Pitch of the beep.")
+                .value_parser(value_parser!(f32))
+                .default_value("440"),
+        )
+        .arg(
+            arg!(--"beep-wave" <WAVE> "Shape of the beep.")
+                .value_parser(["square", "sine", "triangle"])
+                .default_value("square"),
+        )
+        .arg(arg!(--"flip-h" "Mirror the picture horizontally."))
+        .arg(arg!(--"flip-v" "Mirror the picture vertically."))
+        .arg(
+            arg!(--"pixel-format" <FORMAT> "Channel order of the window's pixels.")
+                .value_parser(["rgb", "bgr"])
+                .default_value("rgb"),
+        )
+        .arg(arg!(--seed <SEED> "Seed for the random number generator.").value_parser(value_parser!(u64)))
+        .arg(
+            arg!(--ips <N> "Average N instructions per second instead of a fixed ipf.")
+                .value_parser(value_parser!(u32).range(1..))
+                .conflicts_with_all(["record", "replay"]),
+        )
+        .arg(arg!(--"quirk-report" "List the quirk-dependent instructions that ran on exit."))
+        .arg(
+            arg!(--"skip-boot" <N> "Run N instructions at full speed before opening the window.")
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(--record <FILE> "Record key presses to a replay file.")
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--replay <FILE> "Play back key presses from a replay file, ignoring the keyboard.")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with_all(["record", "seed"]),
+        )
+        .subcommand(
+            Command::new("bench")
+                .about("Measure raw interpreter throughput without a window.")
+                .arg(arg!(<FILE> "Chip-8 program to execute.").value_parser(value_parser!(PathBuf)))
+                .arg(
+                    arg!(--instructions <N> "Number of instructions to execute.")
+                        .value_parser(value_parser!(u64))
+                        .default_value("10000000"),
+                )
+                .arg(arg!(--"by-opcode" "Also report the time spent in each opcode family.")),
+        )
+        .subcommand(
+            Command::new("vdiff")
+                .about("Compare two programs' displays after running each for N instructions.")
+                .arg(arg!(<A> "First program.").value_parser(value_parser!(PathBuf)))
+                .arg(arg!(<B> "Second program.").value_parser(value_parser!(PathBuf)))
+                .arg(
+                    arg!(--steps <N> "Number of instructions to execute.")
+                        .value_parser(value_parser!(u64))
+                        .default_value("100000"),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Print the size, entry point, and detected features of a program.")
+                .arg(
+                    arg!(<FILE> "Chip-8 program to inspect.").value_parser(value_parser!(PathBuf)),
+                ),
+        )
         .get_matches();
 
+    match matches.subcommand() {
+        Some(("bench", m)) => bench::run(m),
+        Some(("info", m)) => info::run(m),
+        Some(("vdiff", m)) => vdiff::run(m),
+        _ => run(&matches),
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
     let file: &PathBuf = matches.get_one("FILE").expect("FILE is required");
+    let program = fs::read(file).with_context(|| format!("Unable to read {}", file.display()))?;
+
+    let profile = match matches.get_one::<String>("profile") {
+        Some(name) => Profile::load(name)?,
+        None if matches.get_flag("auto-quirks") => {
+            let platform = Platform::detect(&program);
+            let name = profile::builtin_for(platform);
+            if platform == Platform::Chip8 {
+                eprintln!("warning: no SUPER-CHIP or XO-CHIP instructions found, assuming {name}");
+            }
+            println!("Using the {name} profile.");
+            Profile::load(name)?
+        }
+        None => Profile::default(),
+    };
+
+    let mut palette = profile.palette();
+    for (n, color) in palette.colors.iter_mut().enumerate() {
+        if let Some(c) = matches.get_one::<u32>(&format!("color{n}")) {
+            *color = *c;
+        }
+    }
+
+    let mut c8 = C8::with_quirks(profile.quirks());
+    c8.set_palette(palette);
+    c8.set_fade(matches.get_flag("fade"));
+    c8.set_frame_blend(matches.get_flag("blend"));
+    c8.set_platform(
+        matches
+            .get_one::<String>("platform")
+            .map(|p| match p.as_str() {
+                "schip" => Platform::SuperChip,
+                "xochip" => Platform::XoChip,
+                _ => Platform::Chip8,
+            }),
+    );
+    c8.set_rotation(
+        match matches.get_one::<String>("rotate").map(String::as_str) {
+            Some("90") => Rotation::Cw90,
+            Some("180") => Rotation::Cw180,
+            Some("270") => Rotation::Cw270,
+            _ => Rotation::None,
+        },
+    );
+    c8.set_beep(BeepConfig {
+        waveform: match matches.get_one::<String>("beep-wave").map(String::as_str) {
+            Some("sine") => Waveform::Sine,
+            Some("triangle") => Waveform::Triangle,
+            _ => Waveform::Square,
+        },
+        frequency: *matches.get_one::<f32>("beep-freq").unwrap(),
+        ..c8.beep()
+    });
+    c8.set_quirk_tracking(matches.get_flag("quirk-report"));
+    c8.set_flip(matches.get_flag("flip-h"), matches.get_flag("flip-v"));
+    c8.set_draw_limit(matches.get_one::<u64>("draw-limit").map(|n| *n as usize));
+    c8.set_pixel_format(
+        match matches
+            .get_one::<String>("pixel-format")
+            .map(String::as_str)
+        {
+            Some("bgr") => PixelFormat::Bgr,
+            _ => PixelFormat::Rgb,
+        },
+    );
+
+    let record_file = matches.get_one::<PathBuf>("record");
+    let replay_file = matches.get_one::<PathBuf>("replay");
+    let seed = matches.get_one::<u64>("seed").copied();
+    let mut ipf = profile.ipf;
+    if let Some(path) = replay_file {
+        let replay = replay::load(path)?;
+        c8.apply_replay(&program, &replay)?;
+        ipf = replay.ipf;
+    } else if record_file.is_some() {
+        c8.start_replay(&program, seed.unwrap_or_else(replay::random_seed), ipf)?;
+    } else {
+        c8.load_bytes(&program)?;
+        if let Some(seed) = seed {
+            c8.set_seed(seed);
+        }
+    }
+
+    // recordings count timers down once per frame rather than by the wall clock so that they
+    // play back identically
+    let deterministic = record_file.is_some() || replay_file.is_some();
 
-    let mut c8 = C8::new();
-    c8.load_program(file)?;
-    for _ in 0..10 {
-        c8.tick();
+    let target_ips = matches.get_one::<u32>("ips");
+    if let Some(ips) = target_ips {
+        c8.set_target_ips(*ips);
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .context("Unable to handle Ctrl-C")?;
+    }
+
+    if let Some(n) = matches.get_one::<u64>("skip-boot") {
+        skip_boot(&mut c8, *n, ipf, &interrupted).context("Program stopped")?;
+    }
+    c8.run_until_first_draw(STARTUP_INSTRUCTION_CAP)
+        .context("Program stopped")?;
+    if interrupted.load(Ordering::SeqCst) {
+        return save_interrupted(&c8);
     }
 
     let mut window = Window::new(
-        "C8",
-        WIDTH,
-        HEIGHT,
+        TITLE,
+        c8.frame_width(),
+        c8.frame_height(),
         WindowOptions {
             resize: true,
             scale: minifb::Scale::FitScreen,
@@ -63,26 +371,133 @@ fn main() -> Result<()> {
 
     window.limit_update_rate(Some(Duration::from_secs(1) / 60));
 
-    let mut buf = [0; WIDTH * HEIGHT];
+    let mut buf = vec![0; c8.frame_width() * c8.frame_height()];
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = match gamepad::Gamepad::new(&profile.gamepad) {
+        Ok(gamepad) => Some(gamepad),
+        Err(e) => {
+            eprintln!("warning: {e}");
+            None
+        }
+    };
+
+    let mut keypad = [false; 16];
+    let mut focused = true;
+    let mut clear_title_at = None;
     while window.is_open() && !window.is_key_down(Key::Escape) {
-        keys_to_key_codes(&window.get_keys_pressed(KeyRepeat::No))
-            .iter()
-            .for_each(|k| c8.key_pressed(*k, true));
+        if interrupted.load(Ordering::SeqCst) {
+            save_interrupted(&c8)?;
+            break;
+        }
 
-        keys_to_key_codes(&window.get_keys_released())
-            .iter()
-            .for_each(|k| c8.key_pressed(*k, false));
+        if let Some(message) = slots::handle_keys(&window, &mut c8, file, !deterministic) {
+            window.set_title(&format!("{TITLE} - {message}"));
+            clear_title_at = Some(Instant::now() + SLOT_MESSAGE_TIME);
+        }
+        if clear_title_at.is_some_and(|at| Instant::now() >= at) {
+            window.set_title(TITLE);
+            clear_title_at = None;
+        }
 
-        for _ in 0..10 {
-            c8.tick();
+        // pause while another window has focus, letting go of every key since their releases
+        // would be lost
+        let active = window.is_active();
+        if active != focused {
+            if !active && replay_file.is_none() {
+                c8.release_all_keys();
+                keypad = [false; 16];
+            }
+            c8.pause_timers(!active);
+            window.set_title(if active { TITLE } else { PAUSED_TITLE });
+            focused = active;
+        }
+        if !active {
+            window.update();
+            continue;
         }
 
-        c8.render(&mut buf);
+        if replay_file.is_none() {
+            #[allow(unused_mut)]
+            let mut state = keypad_state(&window.get_keys());
+            #[cfg(feature = "gamepad")]
+            if let Some(gamepad) = &mut gamepad {
+                for (key, pressed) in state.iter_mut().zip(gamepad.keypad_state()) {
+                    *key |= pressed;
+                }
+            }
+            for (k, (was, is)) in keypad.iter().zip(state.iter()).enumerate() {
+                if was != is {
+                    c8.key_pressed(k, *is);
+                }
+            }
+            keypad = state;
+        }
+
+        if deterministic {
+            c8.run_frame(ipf, &mut buf).context("Program stopped")?;
+        } else {
+            // a frame boundary, so once per frame however many instructions run
+            c8.update_timers();
+            if target_ips.is_some() {
+                c8.run_for(usize::MAX).context("Program stopped")?;
+            } else {
+                for _ in 0..ipf {
+                    c8.try_step().context("Program stopped")?;
+                }
+            }
+            c8.render(&mut buf);
+        }
+
+        if c8.is_halted() {
+            println!("Program exited.");
+            break;
+        }
 
         window
-            .update_with_buffer(&buf, WIDTH, HEIGHT)
+            .update_with_buffer(&buf, c8.frame_width(), c8.frame_height())
             .context("Failed to update display.")?
     }
 
+    for quirk_use in c8.quirk_report() {
+        println!("{quirk_use}.");
+    }
+
+    if let (Some(path), Some(replay)) = (record_file, c8.replay()) {
+        replay::save(path, &replay)?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_boot_counts_timers_down_once_per_frame() {
+        let mut c8 = C8::new();
+        // LD V0, 0x20; LD DT, V0; JP 0x204
+        c8.load_bytes(&[0x60, 0x20, 0xf0, 0x15, 0x12, 0x04])
+            .unwrap();
+        skip_boot(&mut c8, 30, 10, &AtomicBool::new(false)).unwrap();
+        let state = c8.save_state();
+        assert_eq!(state.cycles, 30);
+        assert_eq!(state.delay, 0x20 - 3);
+    }
+
+    #[test]
+    fn skip_boot_stops_on_errors() {
+        let mut c8 = C8::new();
+        c8.load_bytes(&[0x00, 0xe0, 0xff, 0xff]).unwrap();
+        assert!(skip_boot(&mut c8, 10, 10, &AtomicBool::new(false)).is_err());
+    }
+
+    #[test]
+    fn skip_boot_stops_on_ctrl_c() {
+        let mut c8 = C8::new();
+        // JP 0x200
+        c8.load_bytes(&[0x12, 0x00]).unwrap();
+        skip_boot(&mut c8, 1_000, 10, &AtomicBool::new(true)).unwrap();
+        assert_eq!(c8.cycles(), 0);
+    }
+}
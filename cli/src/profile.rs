@@ -0,0 +1,142 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{Context, Result};
+use interpreter::{Palette, Platform, Quirks};
+use serde::Deserialize;
+
+use crate::INSTRUCTIONS_PER_FRAME;
+
+/// Per-game configuration, loaded from a TOML file or chosen by name from the built-ins.
+///
+/// ```toml
+/// ipf = 10
+///
+/// [quirks]
+/// shift_vy = true
+///
+/// [palette]
+/// colors = [0x000000, 0xffffff]
+///
+/// # with the `gamepad` feature
+/// [gamepad]
+/// South = 0x5
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Instructions executed per 60Hz frame
+    pub ipf: usize,
+    quirks: Quirks,
+    palette: PaletteProfile,
+    /// Chip-8 keys for gamepad buttons, overriding the defaults
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    pub gamepad: HashMap<String, usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PaletteProfile {
+    /// Overrides for the leading entries of the default palette
+    colors: Vec<u32>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            ipf: INSTRUCTIONS_PER_FRAME,
+            quirks: Default::default(),
+            palette: Default::default(),
+            gamepad: Default::default(),
+        }
+    }
+}
+
+impl Profile {
+    /// Load the built-in profile called `name`, or else the TOML file at that path. `cosmic` is
+    /// accepted for `cosmac`.
+    pub fn load(name: &str) -> Result<Self> {
+        match name {
+            "cosmac" | "cosmic" => Ok(Self::builtin(Platform::Chip8)),
+            "schip" => Ok(Self::builtin(Platform::SuperChip)),
+            "xochip" => Ok(Self::builtin(Platform::XoChip)),
+            path => {
+                let path = Path::new(path);
+                let s = fs::read_to_string(path)
+                    .with_context(|| format!("Unable to read profile {}", path.display()))?;
+                toml::from_str(&s).with_context(|| format!("Invalid profile {}", path.display()))
+            }
+        }
+    }
+
+    /// The interpreter's defaults for `platform`
+    fn builtin(platform: Platform) -> Self {
+        Self {
+            ipf: platform.instructions_per_frame(),
+            quirks: Quirks::for_platform(platform),
+            ..Default::default()
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn palette(&self) -> Palette {
+        let mut palette = Palette::default();
+        for (color, c) in palette.colors.iter_mut().zip(&self.palette.colors) {
+            *color = *c;
+        }
+        palette
+    }
+}
+
+/// Name of the built-in profile for programs targeting `platform`
+pub fn builtin_for(platform: Platform) -> &'static str {
+    match platform {
+        Platform::Chip8 => "cosmac",
+        Platform::SuperChip => "schip",
+        Platform::XoChip => "xochip",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cosmic_is_an_alias_for_cosmac() {
+        let cosmic = Profile::load("cosmic").unwrap();
+        let cosmac = Profile::load("cosmac").unwrap();
+        assert_eq!(cosmic.quirks(), cosmac.quirks());
+        assert_eq!(cosmic.quirks(), Quirks::for_platform(Platform::Chip8));
+        assert_eq!(cosmic.ipf, cosmac.ipf);
+    }
+
+    #[test]
+    fn auto_quirks_picks_the_detected_platforms_profile() {
+        // CLS; HIGH; JP 0x202
+        let schip = [0x00, 0xe0, 0x00, 0xff, 0x12, 0x02];
+        let name = builtin_for(Platform::detect(&schip));
+        assert_eq!(name, "schip");
+        let profile = Profile::load(name).unwrap();
+        assert_eq!(profile.quirks(), Quirks::for_platform(Platform::SuperChip));
+        assert_eq!(profile.ipf, Platform::SuperChip.instructions_per_frame());
+
+        // CLS; JP 0x202
+        let chip8 = [0x00, 0xe0, 0x12, 0x02];
+        assert_eq!(builtin_for(Platform::detect(&chip8)), "cosmac");
+    }
+
+    #[test]
+    fn quirks_missing_from_a_profile_default_to_off() {
+        let profile: Profile = toml::from_str("[quirks]\ndraw_16x16 = true").unwrap();
+        assert_eq!(
+            profile.quirks(),
+            Quirks {
+                draw_16x16: true,
+                ..Default::default()
+            }
+        );
+        assert!(toml::from_str::<Profile>("[quirks]\nshift_vx = true").is_err());
+    }
+}
@@ -0,0 +1,28 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use interpreter::Replay;
+
+/// Read a replay written by `save`
+pub fn load(path: &Path) -> Result<Replay> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read replay {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid replay {}", path.display()))
+}
+
+pub fn save(path: &Path, replay: &Replay) -> Result<()> {
+    let json = serde_json::to_string(replay).context("Unable to serialize replay")?;
+    fs::write(path, json).with_context(|| format!("Unable to write replay {}", path.display()))
+}
+
+/// Seed for a new recording when none is given
+pub fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,54 @@
+//! Quick-save slots: F1-F4 save to slots 1-4 and Shift+F1-F4 load from them
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use interpreter::{State, C8};
+use minifb::{Key, KeyRepeat, Window};
+
+/// Where slot `n` for `program` is kept: next to it, e.g. `pong.slot1.json` for `pong.ch8`
+fn path(program: &Path, n: usize) -> PathBuf {
+    let stem = program.file_stem().unwrap_or_default().to_string_lossy();
+    program.with_file_name(format!("{stem}.slot{n}.json"))
+}
+
+fn save(c8: &C8, program: &Path, n: usize) -> Result<()> {
+    let path = path(program, n);
+    fs::write(&path, c8.to_json()).with_context(|| format!("Unable to write {}", path.display()))
+}
+
+fn load(c8: &mut C8, program: &Path, n: usize) -> Result<()> {
+    let path = path(program, n);
+    let json =
+        fs::read_to_string(&path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let state: State =
+        serde_json::from_str(&json).with_context(|| format!("Invalid {}", path.display()))?;
+    c8.load_state(&state)
+        .with_context(|| format!("Unable to load {}", path.display()))
+}
+
+/// Save or load a slot if one of its keys was just pressed, returning a message saying what
+/// happened. Loading is refused unless `can_load`, e.g. while recording.
+pub fn handle_keys(window: &Window, c8: &mut C8, program: &Path, can_load: bool) -> Option<String> {
+    let n = window
+        .get_keys_pressed(KeyRepeat::No)
+        .iter()
+        .find_map(|key| match key {
+            Key::F1 => Some(1),
+            Key::F2 => Some(2),
+            Key::F3 => Some(3),
+            Key::F4 => Some(4),
+            _ => None,
+        })?;
+
+    let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+    let result = match (shift, can_load) {
+        (false, _) => save(c8, program, n).map(|()| format!("Saved slot {n}")),
+        (true, true) => load(c8, program, n).map(|()| format!("Loaded slot {n}")),
+        (true, false) => Ok("Can't load while recording or replaying".to_string()),
+    };
+    Some(result.unwrap_or_else(|e| format!("{e:#}")))
+}
@@ -0,0 +1,57 @@
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use interpreter::C8;
+
+use crate::INSTRUCTIONS_PER_FRAME;
+
+/// Run `file` headlessly for `steps` instructions, counting the timers down once a frame, and
+/// return its display
+fn run_headless(file: &Path, steps: u64) -> Result<String> {
+    let mut c8 = C8::new();
+    c8.load_program(file)?;
+    // both programs see the same random numbers, so only their code can make them differ
+    c8.set_seed(0);
+    for n in 1..=steps {
+        if c8.is_halted() {
+            break;
+        }
+        c8.try_step()
+            .with_context(|| format!("{} stopped", file.display()))?;
+        if n % INSTRUCTIONS_PER_FRAME as u64 == 0 {
+            c8.decrement_timers();
+        }
+    }
+    Ok(c8.render_ascii())
+}
+
+/// Print two programs' displays side by side after running each for the same number of
+/// instructions, followed by a map of the pixels that differ. Exits with status 1 if any do.
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    let a: &PathBuf = matches.get_one("A").expect("A is required");
+    let b: &PathBuf = matches.get_one("B").expect("B is required");
+    let steps: u64 = *matches.get_one("steps").expect("steps has a default");
+
+    let (display_a, display_b) = (run_headless(a, steps)?, run_headless(b, steps)?);
+
+    let mut differing = 0;
+    for (row_a, row_b) in display_a.lines().zip(display_b.lines()) {
+        let diff: String = row_a
+            .chars()
+            .zip(row_b.chars())
+            .map(|(pa, pb)| if pa == pb { '.' } else { 'X' })
+            .collect();
+        differing += diff.matches('X').count();
+        println!("{row_a}  {row_b}  {diff}");
+    }
+    println!("{differing} pixels differ");
+
+    if differing > 0 {
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+//! Run arbitrary programs, checking that nothing a program does can panic the interpreter.
+//!
+//! With `cargo-fuzz` installed and a nightly toolchain, run from the `interpreter` directory:
+//!
+//! ```sh
+//! cargo +nightly fuzz run step fuzz/corpus/step
+//! ```
+//!
+//! The corpus only holds small hand-written programs. Real ROMs find more paths quickly; copy
+//! any you have into a scratch directory and pass it after the corpus, e.g.
+//! `cargo +nightly fuzz run step fuzz/corpus/step ~/roms`, rather than committing them.
+
+#![no_main]
+
+use interpreter::C8;
+use libfuzzer_sys::fuzz_target;
+
+/// Instructions to run per input, enough to get through a few loops
+const BUDGET: usize = 10_000;
+
+fuzz_target!(|program: &[u8]| {
+    let mut c8 = C8::new();
+    c8.set_seed(0);
+    if c8.load_bytes(program).is_err() {
+        return;
+    }
+
+    for _ in 0..BUDGET {
+        if c8.is_halted() || c8.try_step().is_err() {
+            break;
+        }
+    }
+});
@@ -0,0 +1,221 @@
+use std::f32::consts::TAU;
+
+use crate::{C8, DEFAULT_MEM_SIZE};
+
+/// XO-CHIP pitch register value that plays the audio pattern at 4000 bits per second
+pub(crate) const DEFAULT_PITCH: u8 = 64;
+
+/// Bits in the XO-CHIP audio pattern
+const PATTERN_BITS: f32 = 128.0;
+
+/// Rate in bits per second the XO-CHIP audio pattern plays at for `pitch`
+fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// Shape of the tone played while the sound timer is active
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// Level from -1 to 1 at `phase`, a fraction of the way through a cycle
+    fn level(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+/// Tone `sound_active_samples` generates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeepConfig {
+    pub waveform: Waveform,
+    /// In Hz
+    pub frequency: f32,
+    /// Peak amplitude, from 0 to 1
+    pub volume: f32,
+}
+
+impl Default for BeepConfig {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            volume: 0.25,
+        }
+    }
+}
+
+impl BeepConfig {
+    /// Sample at `phase`, a fraction of the way through a cycle
+    pub(crate) fn sample(&self, phase: f32) -> f32 {
+        self.waveform.level(phase) * self.volume
+    }
+
+    /// Sample of an XO-CHIP audio pattern at `phase`, a fraction of the way through the pattern
+    pub(crate) fn pattern_sample(&self, pattern: &[u8; 16], phase: f32) -> f32 {
+        let bit = (phase * PATTERN_BITS) as usize % 128;
+        if pattern[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+            self.volume
+        } else {
+            -self.volume
+        }
+    }
+}
+
+impl C8 {
+    pub fn beep(&self) -> BeepConfig {
+        self.beep
+    }
+
+    /// The XO-CHIP audio pattern loaded by `F002`, if any. Once loaded it replaces the beep,
+    /// keeping only its volume.
+    pub fn audio_pattern(&self) -> Option<[u8; 16]> {
+        self.audio_pattern
+    }
+
+    /// The XO-CHIP pitch register set by `FX3A`
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Fraction of a cycle of the beep, or of the whole audio pattern, played per sample
+    pub(crate) fn audio_step(&self, sample_rate: u32) -> f32 {
+        match self.audio_pattern {
+            Some(_) => pattern_rate(self.pitch) / PATTERN_BITS / sample_rate as f32,
+            None => self.beep.frequency / sample_rate as f32,
+        }
+    }
+
+    pub(crate) fn audio_sample(&self, phase: f32) -> f32 {
+        match &self.audio_pattern {
+            Some(pattern) => self.beep.pattern_sample(pattern, phase),
+            None => self.beep.sample(phase),
+        }
+    }
+
+    /// Load the 16-byte audio pattern at I (`F002`)
+    pub(crate) fn load_audio_pattern(&mut self) {
+        let mut pattern = [0; 16];
+        for (n, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.memory[(self.i + n) % DEFAULT_MEM_SIZE];
+        }
+        self.audio_pattern = Some(pattern);
+    }
+
+    /// Set the rate the audio pattern plays at from VX (`FX3A`)
+    pub(crate) fn set_pitch(&mut self, x: usize) {
+        self.pitch = self.reg[x];
+    }
+
+    /// Change the tone generated by `sound_active_samples`. The volume is clamped to 0-1 and
+    /// the frequency to be non-negative.
+    pub fn set_beep(&mut self, beep: BeepConfig) {
+        self.beep = BeepConfig {
+            frequency: beep.frequency.max(0.0),
+            volume: beep.volume.clamp(0.0, 1.0),
+            ..beep
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn waveform_levels() {
+        assert_eq!(Waveform::Square.level(0.25), 1.0);
+        assert_eq!(Waveform::Square.level(0.75), -1.0);
+        assert!(close(Waveform::Sine.level(0.25), 1.0));
+        assert!(close(Waveform::Sine.level(0.5), 0.0));
+        assert!(close(Waveform::Sine.level(0.75), -1.0));
+        assert!(close(Waveform::Triangle.level(0.0), -1.0));
+        assert!(close(Waveform::Triangle.level(0.25), 0.0));
+        assert!(close(Waveform::Triangle.level(0.5), 1.0));
+    }
+
+    #[test]
+    fn samples_are_scaled_by_the_volume() {
+        let beep = BeepConfig {
+            waveform: Waveform::Sine,
+            volume: 0.5,
+            ..Default::default()
+        };
+        assert!(close(beep.sample(0.25), 0.5));
+        assert!(close(beep.sample(0.75), -0.5));
+    }
+
+    #[test]
+    fn set_beep_clamps_volume_and_frequency() {
+        let mut c8 = C8::new();
+        c8.set_beep(BeepConfig {
+            waveform: Waveform::Triangle,
+            frequency: -1.0,
+            volume: 2.0,
+        });
+        assert_eq!(
+            c8.beep(),
+            BeepConfig {
+                waveform: Waveform::Triangle,
+                frequency: 0.0,
+                volume: 1.0,
+            }
+        );
+        assert!(close(c8.audio_step(44_100), 0.0));
+    }
+
+    #[test]
+    fn patterns_play_most_significant_bit_first() {
+        let beep = BeepConfig::default();
+        let mut pattern = [0; 16];
+        pattern[0] = 0b1000_0001;
+        pattern[15] = 0x01;
+        let bit = |n: f32| beep.pattern_sample(&pattern, n / PATTERN_BITS);
+        assert_eq!(bit(0.0), beep.volume);
+        assert_eq!(bit(1.0), -beep.volume);
+        assert_eq!(bit(7.0), beep.volume);
+        assert_eq!(bit(126.0), -beep.volume);
+        assert_eq!(bit(127.0), beep.volume);
+    }
+
+    #[test]
+    fn f002_and_fx3a_set_the_pattern_and_pitch() {
+        let mut c8 = C8::new();
+        // LD I, 0x300; LD AUDIO, [I]; LD V1, 0x70; LD PITCH, V1
+        c8.load_bytes(&[0xa3, 0x00, 0xf0, 0x02, 0x61, 0x70, 0xf1, 0x3a])
+            .unwrap();
+        for (n, byte) in c8.memory[0x300..0x310].iter_mut().enumerate() {
+            *byte = n as u8;
+        }
+        assert_eq!(c8.audio_pattern(), None);
+        c8.step();
+        c8.step();
+        let pattern: [u8; 16] = std::array::from_fn(|n| n as u8);
+        assert_eq!(c8.audio_pattern(), Some(pattern));
+        assert!(close(c8.audio_step(4000), 1.0 / PATTERN_BITS));
+
+        c8.step();
+        c8.step();
+        assert_eq!(c8.pitch(), 0x70);
+        // 48 steps of pitch double the rate
+        assert!(close(c8.audio_step(4000), 2.0 / PATTERN_BITS));
+    }
+}
@@ -0,0 +1,124 @@
+#[cfg(feature = "logging")]
+use log::warn;
+
+use crate::{disasm::disassemble, C8};
+
+/// Subroutine nesting seen since tracking started
+pub(crate) struct CallDepth {
+    /// Depth past which a call counts as suspicious
+    warn_at: usize,
+    max: usize,
+    warnings: u64,
+}
+
+impl C8 {
+    /// Track how deeply subroutines nest, logging a warning each time a call takes the stack
+    /// past `warn_at`, which usually means runaway recursion or a jump out of a subroutine that
+    /// never returns. `None` turns tracking off. Turning it on resets the counts.
+    ///
+    /// Returning with more `ret`s than calls always fails with `C8Error::StackUnderflow`.
+    pub fn set_call_depth_warning(&mut self, warn_at: Option<usize>) {
+        self.call_depth = warn_at.map(|warn_at| CallDepth {
+            warn_at,
+            max: self.stack.len(),
+            warnings: 0,
+        });
+    }
+
+    /// Number of subroutines currently on the stack
+    pub fn call_depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Return addresses on the stack, innermost call first
+    pub fn call_stack(&self) -> Vec<usize> {
+        self.stack.iter().copied().collect()
+    }
+
+    /// The address and disassembly of each call still on the stack, innermost first, found from
+    /// the return addresses
+    pub fn backtrace(&self) -> Vec<(usize, String)> {
+        let len = self.memory.len();
+        self.stack
+            .iter()
+            .map(|ret| {
+                let at = ret.wrapping_sub(2) % len;
+                let opcode = u16::from_be_bytes([self.memory[at], self.memory[(at + 1) % len]]);
+                (at, disassemble(opcode))
+            })
+            .collect()
+    }
+
+    /// Deepest nesting seen since tracking was turned on
+    pub fn max_call_depth(&self) -> Option<usize> {
+        self.call_depth.as_ref().map(|d| d.max)
+    }
+
+    /// Number of calls that went past the warning threshold
+    pub fn call_depth_warnings(&self) -> u64 {
+        self.call_depth.as_ref().map_or(0, |d| d.warnings)
+    }
+
+    /// Record a call that left the stack `depth` deep
+    pub(crate) fn note_call(&mut self, depth: usize) {
+        if let Some(tracking) = &mut self.call_depth {
+            tracking.max = tracking.max.max(depth);
+            if depth > tracking.warn_at {
+                tracking.warnings += 1;
+                warn!(
+                    "call to {:#05x} nests subroutines {depth} deep, past {}",
+                    self.pc, tracking.warn_at
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backtraces_list_the_innermost_call_first() {
+        let mut c8 = C8::new();
+        c8.load_bytes(&[
+            0x22, 0x04, // 0x200: CALL 0x204
+            0x00, 0x00, //
+            0x22, 0x08, // 0x204: CALL 0x208
+            0x00, 0x00, //
+            0x12, 0x08, // 0x208: JP 0x208
+        ])
+        .unwrap();
+        c8.step();
+        c8.step();
+        assert_eq!(c8.call_stack(), [0x206, 0x202]);
+        assert_eq!(
+            c8.backtrace(),
+            [
+                (0x204, "CALL 0x208".to_string()),
+                (0x200, "CALL 0x204".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn calls_past_the_threshold_are_counted() {
+        let mut c8 = C8::new();
+        // 0x200: CALL 0x200
+        c8.load_bytes(&[0x22, 0x00]).unwrap();
+        assert_eq!(c8.max_call_depth(), None);
+
+        c8.set_call_depth_warning(Some(2));
+        for _ in 0..5 {
+            c8.step();
+        }
+        assert_eq!(c8.call_depth(), 5);
+        assert_eq!(c8.max_call_depth(), Some(5));
+        assert_eq!(c8.call_depth_warnings(), 3);
+
+        // turning it back on starts from the current depth
+        c8.set_call_depth_warning(Some(8));
+        assert_eq!(c8.max_call_depth(), Some(5));
+        assert_eq!(c8.call_depth_warnings(), 0);
+    }
+}
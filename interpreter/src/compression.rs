@@ -0,0 +1,83 @@
+use std::{io::Read, path::Path};
+
+use flate2::read::GzDecoder;
+
+use crate::{error::Result, DEFAULT_MEM_SIZE};
+
+/// Bytes every gzip file starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Decompress `bytes` read from `path` if they're gzipped, judging by the extension or the magic
+/// bytes, or else return them as they are.
+///
+/// A program can legitimately start with the magic bytes (`1F8B` is a jump), so without the
+/// extension data that doesn't decompress is loaded raw.
+pub fn decompress(path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let gz_extension = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"));
+    if !gz_extension && !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+
+    // anything bigger than memory is rejected when loaded, so don't inflate further than that
+    let mut program = Vec::with_capacity(DEFAULT_MEM_SIZE);
+    match GzDecoder::new(&bytes[..])
+        .take(DEFAULT_MEM_SIZE as u64 + 1)
+        .read_to_end(&mut program)
+    {
+        Ok(_) => Ok(program),
+        Err(e) if gz_extension => Err(e.into()),
+        Err(_) => Ok(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io::Write, process};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+    use crate::{C8, PROGRAM_START};
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn gzipped_programs_are_decompressed() {
+        let program = [0x60, 0x05, 0x12, 0x02];
+        let gz = gzip(&program);
+        assert_eq!(
+            decompress(Path::new("a.ch8.gz"), gz.clone()).unwrap(),
+            program
+        );
+        // by the magic bytes alone
+        assert_eq!(decompress(Path::new("a.ch8"), gz).unwrap(), program);
+    }
+
+    #[test]
+    fn raw_programs_pass_through() {
+        // JP 0xF8B, which starts with the gzip magic bytes
+        let program = vec![0x1f, 0x8b, 0x00, 0x00];
+        assert_eq!(
+            decompress(Path::new("a.ch8"), program.clone()).unwrap(),
+            program
+        );
+        assert!(decompress(Path::new("a.ch8.gz"), program).is_err());
+    }
+
+    #[test]
+    fn load_program_reads_gzipped_files() {
+        let path = env::temp_dir().join(format!("c8-compression-{}.ch8.gz", process::id()));
+        fs::write(&path, gzip(&[0x60, 0x05])).unwrap();
+        let mut c8 = C8::new();
+        let loaded = c8.load_program(&path);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap();
+        assert_eq!(c8.memory[PROGRAM_START..PROGRAM_START + 2], [0x60, 0x05]);
+    }
+}
@@ -0,0 +1,93 @@
+#[cfg(feature = "logging")]
+use log::warn;
+
+use crate::{C8, DEFAULT_MEM_SIZE};
+
+impl C8 {
+    /// Record which addresses have been executed as instructions, for `coverage`. Turning
+    /// tracking on or off resets the map.
+    pub fn set_coverage(&mut self, enabled: bool) {
+        self.coverage = enabled.then(|| vec![false; DEFAULT_MEM_SIZE]);
+    }
+
+    /// Whether each byte of memory has been executed as part of an instruction since tracking
+    /// was turned on
+    pub fn coverage(&self) -> Option<&[bool]> {
+        self.coverage.as_deref()
+    }
+
+    /// Call `hook` with the address whenever the program writes to memory it has already
+    /// executed, a sign that it modifies its own code and any disassembly of it may be stale.
+    /// The write is logged as a warning too.
+    ///
+    /// Only fires while coverage is being tracked, since that's what records the executed
+    /// addresses.
+    pub fn set_self_modify_hook(&mut self, hook: Option<Box<dyn FnMut(usize)>>) {
+        self.self_modify_hook = hook;
+    }
+
+    /// Mark the instruction at `addr` as executed
+    pub(crate) fn note_executed(&mut self, addr: usize) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage[addr % DEFAULT_MEM_SIZE] = true;
+            coverage[(addr + 1) % DEFAULT_MEM_SIZE] = true;
+        }
+    }
+
+    /// Check a write to `addr` against the executed addresses
+    pub(crate) fn note_write(&mut self, addr: usize) {
+        if !self
+            .coverage
+            .as_ref()
+            .is_some_and(|coverage| coverage[addr])
+        {
+            return;
+        }
+        warn!("write to {addr:#05x}, which has been executed");
+        if let Some(hook) = &mut self.self_modify_hook {
+            hook(addr);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    /// Run a program that overwrites its first instruction with `FX55`, returning the addresses
+    /// the self-modify hook saw
+    fn self_modifying_run(coverage: bool) -> Vec<usize> {
+        let mut c8 = C8::new();
+        c8.load_bytes(&[
+            0x60, 0x12, // 0x200: LD V0, 0x12
+            0x61, 0x00, // 0x202: LD V1, 0x00
+            0xa2, 0x00, // 0x204: LD I, 0x200
+            0xf1, 0x55, // 0x206: LD [I], V1
+        ])
+        .unwrap();
+        c8.set_coverage(coverage);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let hook_seen = seen.clone();
+        c8.set_self_modify_hook(Some(Box::new(move |addr| {
+            hook_seen.borrow_mut().push(addr)
+        })));
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.memory[0x200..0x202], [0x12, 0x00]);
+        let seen = seen.borrow().clone();
+        seen
+    }
+
+    #[test]
+    fn overwriting_executed_code_calls_the_hook() {
+        assert_eq!(self_modifying_run(true), [0x200, 0x201]);
+    }
+
+    #[test]
+    fn the_hook_waits_for_coverage() {
+        assert!(self_modifying_run(false).is_empty());
+    }
+}
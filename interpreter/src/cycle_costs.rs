@@ -0,0 +1,22 @@
+/// Cycles charged for each instruction, by the high nibble of its opcode, so `C8::cycles` and
+/// the `run_for` budget can follow the original hardware's timing rather than counting
+/// instructions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CycleCosts {
+    pub families: [u64; 16],
+}
+
+impl Default for CycleCosts {
+    /// One cycle per instruction
+    fn default() -> Self {
+        Self { families: [1; 16] }
+    }
+}
+
+impl CycleCosts {
+    /// Cycles charged for an instruction in `family`. Every instruction costs at least one
+    /// cycle, so budgets always run out.
+    pub fn cost(&self, family: u8) -> u64 {
+        self.families[family as usize & 0xf].max(1)
+    }
+}
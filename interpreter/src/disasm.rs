@@ -0,0 +1,121 @@
+use crate::{AsInstruction, AsU16, PROGRAM_START};
+
+/// Disassemble an opcode into a mnemonic, or `DW` for data the interpreter can't execute
+pub fn disassemble(opcode: u16) -> String {
+    let instruction = ((opcode >> 8) as u8, opcode as u8).as_instruction();
+    match instruction {
+        (0x0, 0x0, 0x0, 0x0) => "NOP".to_string(),
+        (0x0, 0x0, 0xe, 0x0) => "CLS".to_string(),
+        (0x0, 0x0, 0xe, 0xe) => "RET".to_string(),
+        (0x0, 0x0, 0xf, 0xd) => "EXIT".to_string(),
+        (0x1, a, b, c) => format!("JP {:#05X}", (a, b, c).as_u16()),
+        (0x2, a, b, c) => format!("CALL {:#05X}", (a, b, c).as_u16()),
+        (0x3, x, a, b) => format!("SE V{x:X}, {:#04X}", (a, b).as_u16()),
+        (0x4, x, a, b) => format!("SNE V{x:X}, {:#04X}", (a, b).as_u16()),
+        (0x5, x, y, 0) => format!("SE V{x:X}, V{y:X}"),
+        (0x5, x, y, 2) => format!("LD [I], V{x:X}-V{y:X}"),
+        (0x5, x, y, 3) => format!("LD V{x:X}-V{y:X}, [I]"),
+        (0x6, x, a, b) => format!("LD V{x:X}, {:#04X}", (a, b).as_u16()),
+        (0x7, x, a, b) => format!("ADD V{x:X}, {:#04X}", (a, b).as_u16()),
+        (0x8, x, y, 0) => format!("LD V{x:X}, V{y:X}"),
+        (0x8, x, y, 1) => format!("OR V{x:X}, V{y:X}"),
+        (0x8, x, y, 2) => format!("AND V{x:X}, V{y:X}"),
+        (0x8, x, y, 3) => format!("XOR V{x:X}, V{y:X}"),
+        (0x8, x, y, 4) => format!("ADD V{x:X}, V{y:X}"),
+        (0x8, x, y, 5) => format!("SUB V{x:X}, V{y:X}"),
+        (0x8, x, y, 6) => format!("SHR V{x:X}, V{y:X}"),
+        (0x8, x, y, 7) => format!("SUBN V{x:X}, V{y:X}"),
+        (0x8, x, y, 0xe) => format!("SHL V{x:X}, V{y:X}"),
+        (0x9, x, y, 0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xa, a, b, c) => format!("LD I, {:#05X}", (a, b, c).as_u16()),
+        (0xb, a, b, c) => format!("JP V0, {:#05X}", (a, b, c).as_u16()),
+        (0xc, x, a, b) => format!("RND V{x:X}, {:#04X}", (a, b).as_u16()),
+        (0xd, x, y, n) => format!("DRW V{x:X}, V{y:X}, {n}"),
+        (0xe, x, 0x9, 0xe) => format!("SKP V{x:X}"),
+        (0xe, x, 0xa, 0x1) => format!("SKNP V{x:X}"),
+        (0xf, n, 0x0, 0x1) => format!("PLANE {n}"),
+        (0xf, 0x0, 0x0, 0x2) => "AUDIO".to_string(),
+        (0xf, x, 0x3, 0xa) => format!("PITCH V{x:X}"),
+        (0xf, x, 0x0, 0xa) => format!("LD V{x:X}, K"),
+        (0xf, x, 0x3, 0x3) => format!("LD B, V{x:X}"),
+        (0xf, x, 0x5, 0x5) => format!("LD [I], V{x:X}"),
+        (0xf, x, 0x6, 0x5) => format!("LD V{x:X}, [I]"),
+        (0xf, x, 0x7, 0x5) => format!("LD R, V{x:X}"),
+        (0xf, x, 0x8, 0x5) => format!("LD V{x:X}, R"),
+        (0xf, x, 0x2, 0x9) => format!("LD F, V{x:X}"),
+        (0xf, x, 0x1, 0x5) => format!("LD DT, V{x:X}"),
+        (0xf, x, 0x1, 0x8) => format!("LD ST, V{x:X}"),
+        (0xf, x, 0x0, 0x7) => format!("LD V{x:X}, DT"),
+        (0xf, x, 0x1, 0xe) => format!("ADD I, V{x:X}"),
+        _ => format!("DW {opcode:#06X}"),
+    }
+}
+
+/// Most data bytes listed on one `DB` line
+const DATA_PER_LINE: usize = 8;
+
+/// Disassemble a program loaded at `PROGRAM_START`, using `coverage` from `C8::coverage` to tell
+/// code from data. Instructions that were executed are decoded, and every other byte is listed as
+/// `DB` data, so sprites aren't mistaken for instructions. Returns each line's address and text.
+///
+/// Code that didn't run during the coverage run, e.g. a branch that wasn't taken, shows up as
+/// data too.
+pub fn disassemble_with_coverage(program: &[u8], coverage: &[bool]) -> Vec<(usize, String)> {
+    let executed = |offset: usize| coverage.get(PROGRAM_START + offset) == Some(&true);
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < program.len() {
+        let addr = PROGRAM_START + offset;
+        if executed(offset) && offset + 1 < program.len() {
+            let opcode = u16::from_be_bytes([program[offset], program[offset + 1]]);
+            lines.push((addr, disassemble(opcode)));
+            offset += 2;
+        } else {
+            let len = (offset..program.len())
+                .take(DATA_PER_LINE)
+                .take_while(|n| *n == offset || !executed(*n))
+                .count();
+            let bytes: Vec<_> = program[offset..offset + len]
+                .iter()
+                .map(|b| format!("{b:#04X}"))
+                .collect();
+            lines.push((addr, format!("DB {}", bytes.join(", "))));
+            offset += len;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::C8;
+
+    #[test]
+    fn coverage_separates_code_from_data() {
+        let mut program = vec![0x12, 0x0c]; // 0x200: JP 0x20C
+        program.extend([0xf0; 10]); // 0x202: a sprite
+        program.extend([0x60, 0x01]); // 0x20C: LD V0, 0x01
+        program.push(0x12); // 0x20E: JP 0x200, with the low byte past the end of the program
+
+        let mut c8 = C8::new();
+        c8.load_bytes(&program).unwrap();
+        c8.set_coverage(true);
+        for _ in 0..4 {
+            c8.step();
+        }
+
+        let db = |n| format!("DB {}", vec!["0xF0"; n].join(", "));
+        assert_eq!(
+            disassemble_with_coverage(&program, c8.coverage().unwrap()),
+            [
+                (0x200, "JP 0x20C".to_string()),
+                (0x202, db(DATA_PER_LINE)),
+                (0x20a, db(2)),
+                (0x20c, "LD V0, 0x01".to_string()),
+                (0x20e, "DB 0x12".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,40 @@
+/// Pixel grid, each pixel holding the bitmask of the XO-CHIP planes lit there
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Display {
+    width: usize,
+    height: usize,
+    /// Row-major
+    pixels: Vec<u8>,
+}
+
+impl Display {
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_mut(&mut self, c: usize, r: usize) -> &mut u8 {
+        &mut self.pixels[r * self.width + c]
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels
+    }
+}
@@ -2,10 +2,35 @@ use std::{io, result};
 
 use thiserror::Error;
 
+use crate::Platform;
+
 #[derive(Error, Debug)]
 pub enum C8Error {
     #[error("Failed to load the program: {0}")]
     ProgramLoadFailure(#[from] io::Error),
+    #[error("Program is {0} bytes, too large to fit in memory")]
+    ProgramTooLarge(usize),
+    #[error("Program does not match the one the replay was recorded with")]
+    ReplayMismatch,
+    #[error("Unknown instruction {0:04X}")]
+    UnknownInstruction(u16),
+    #[error("Instruction {opcode:04X} isn't available on {platform}")]
+    IllegalForPlatform { opcode: u16, platform: Platform },
+    #[error("Returned from a subroutine with an empty stack")]
+    StackUnderflow,
+    #[error("Program counter {0:#05x} ran off the end of memory")]
+    PcOutOfBounds(usize),
+    #[error("Jump to {addr:#05x}, which is odd or past the end of memory")]
+    MisalignedJump { addr: usize },
+    #[error("Write to {addr:#05x}, which is protected")]
+    ProtectedWrite { addr: usize },
+    #[error("Interpreter panicked: {0}")]
+    Panic(String),
+    #[error("Invalid machine state: {0}")]
+    InvalidState(String),
+    #[cfg(feature = "serde")]
+    #[error("Invalid machine state: {0}")]
+    InvalidJson(#[from] serde_json::Error),
 }
 
 pub type Result<T> = result::Result<T, C8Error>;
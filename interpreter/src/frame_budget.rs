@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+/// Instructions the first frame runs, before anything's been measured
+const INITIAL_INSTRUCTIONS: usize = 10;
+
+/// Most the instruction count grows by in one frame, as a factor, so a single fast frame
+/// doesn't overshoot
+const MAX_GROWTH: f64 = 1.25;
+
+/// Most the instruction count shrinks by in one frame, as a factor
+const MAX_SHRINK: f64 = 0.5;
+
+/// Adapts the instructions run per frame so the time they take approaches a target, running as
+/// many as the host can manage without frames overrunning.
+///
+/// After each frame the count is set to what fits in the target time at the measured time per
+/// instruction, within limits so jitter doesn't make it swing wildly.
+pub struct FrameBudget {
+    target: Duration,
+    instructions: usize,
+}
+
+impl FrameBudget {
+    pub fn new(target: Duration) -> Self {
+        Self {
+            target,
+            instructions: INITIAL_INSTRUCTIONS,
+        }
+    }
+
+    /// Instructions to run in the next frame
+    pub fn instructions(&self) -> usize {
+        self.instructions
+    }
+
+    /// Adjust the count after a frame that ran `executed` instructions in `elapsed`, which may
+    /// be fewer than `instructions()` if it stopped early. A frame that ran nothing tells us
+    /// nothing and is ignored.
+    pub fn record(&mut self, executed: usize, elapsed: Duration) {
+        if executed == 0 {
+            return;
+        }
+        let per_instruction = elapsed.max(Duration::from_nanos(1)).as_secs_f64() / executed as f64;
+        let fits = self.target.as_secs_f64() / per_instruction;
+        let current = self.instructions as f64;
+        let next = fits.clamp(current * MAX_SHRINK, current * MAX_GROWTH);
+        self.instructions = (next.round() as usize).max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget() -> FrameBudget {
+        FrameBudget::new(Duration::from_millis(16))
+    }
+
+    #[test]
+    fn scales_by_the_time_per_instruction() {
+        let mut b = budget();
+        b.record(10, Duration::from_millis(16));
+        assert_eq!(b.instructions(), 10);
+        // stopping early at the same speed isn't mistaken for being fast
+        b.record(5, Duration::from_millis(8));
+        assert_eq!(b.instructions(), 10);
+        b.record(10, Duration::from_millis(20));
+        assert_eq!(b.instructions(), 8);
+    }
+
+    #[test]
+    fn changes_are_limited_per_frame() {
+        let mut b = budget();
+        b.record(1, Duration::from_millis(1));
+        assert_eq!(b.instructions(), 13);
+        b.record(13, Duration::from_secs(1));
+        assert_eq!(b.instructions(), 7);
+    }
+
+    #[test]
+    fn frames_that_ran_nothing_are_ignored() {
+        let mut b = budget();
+        b.record(0, Duration::from_millis(100));
+        assert_eq!(b.instructions(), INITIAL_INSTRUCTIONS);
+    }
+}
@@ -0,0 +1,86 @@
+use std::time::Instant;
+
+/// Longest gap between frames the governor makes up for, in seconds, so a stall doesn't turn
+/// into a burst of catch-up instructions
+const MAX_FRAME: f64 = 0.25;
+
+/// How quickly the estimated frame time follows the measured one
+const SMOOTHING: f64 = 0.1;
+
+/// Share of the accumulated shortfall or excess corrected each frame
+const INTEGRAL_GAIN: f64 = 0.2;
+
+/// Decides how many instructions each frame should run to average a target rate, whatever the
+/// frame rate the front-end manages.
+///
+/// Each frame runs the target rate times a smoothed frame time, so jitter doesn't make the
+/// instruction count jump around, plus a share of the difference between that and what the
+/// measured frame times called for, so nothing is lost over time.
+pub struct Governor {
+    ips: f64,
+    last: Option<Instant>,
+    /// Smoothed frame time in seconds
+    frame: f64,
+    /// Instructions owed (or run ahead, if negative) so far
+    debt: f64,
+    /// Fractional instruction carried over from the last frame
+    remainder: f64,
+}
+
+impl Governor {
+    pub fn new(ips: u32) -> Self {
+        Self {
+            ips: ips.max(1) as f64,
+            last: None,
+            frame: 1.0 / 60.0,
+            debt: 0.0,
+            remainder: 0.0,
+        }
+    }
+
+    /// Instructions to run for the frame starting now
+    pub fn due(&mut self) -> usize {
+        let now = Instant::now();
+        let dt = match self.last.replace(now) {
+            Some(last) => now.duration_since(last).as_secs_f64().min(MAX_FRAME),
+            None => self.frame,
+        };
+        self.due_after(dt)
+    }
+
+    /// Instructions to run for a frame that started `dt` seconds after the last one
+    fn due_after(&mut self, dt: f64) -> usize {
+        self.frame += (dt - self.frame) * SMOOTHING;
+        let planned = self.ips * self.frame;
+        self.debt += self.ips * dt - planned;
+        let correction = self.debt * INTEGRAL_GAIN;
+        self.debt -= correction;
+
+        let n = (planned + correction + self.remainder).max(0.0);
+        self.remainder = n.fract();
+        n as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_averages_the_target_rate_over_jittery_frames() {
+        let mut governor = Governor::new(1000);
+        let (mut total, mut elapsed) = (0, 0.0);
+        for n in 0..600 {
+            // frames alternate between too short and too long, with the odd stall
+            let dt = match n % 10 {
+                9 => 0.05,
+                n if n % 2 == 0 => 0.01,
+                _ => 0.023,
+            };
+            total += governor.due_after(dt);
+            elapsed += dt;
+        }
+        let rate = total as f64 / elapsed;
+        assert!((rate - 1000.0).abs() < 10.0, "{rate} instructions/sec");
+    }
+}
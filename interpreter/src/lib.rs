@@ -1,18 +1,90 @@
+/// Compiled out along with the `log` dependency when the `logging` feature is off. Defined
+/// before the modules so they can use it too.
+#[cfg(not(feature = "logging"))]
+macro_rules! warn {
+    ($($arg:tt)*) => {};
+}
+
+mod beep;
+mod call_depth;
+#[cfg(feature = "compression")]
+mod compression;
+mod coverage;
+mod cycle_costs;
+mod disasm;
+#[cfg(feature = "dispatch-table")]
+mod dispatch;
+mod display;
 mod error;
+mod frame_budget;
+mod governor;
+mod opcodes;
+mod orientation;
+mod palette;
+mod platform;
+mod profiler;
+mod protection;
+mod quirk_report;
+mod quirks;
+mod render;
+mod replay;
+mod sandbox;
+mod state;
+mod step_info;
+#[cfg(any(test, feature = "testing"))]
+pub mod test_util;
+mod throttle;
 mod timer;
+mod write_log;
 
 use std::{
     collections::VecDeque,
+    fmt,
     fs::File,
     io::{BufReader, Read},
+    ops::Range,
     path::Path,
+    time::{Duration, Instant},
 };
 
-use error::Result;
-use log::debug;
-use rand::random;
+use call_depth::CallDepth;
+use display::Display;
+use error::{C8Error, Result};
+use frame_budget::FrameBudget;
+use governor::Governor;
+#[cfg(feature = "logging")]
+use log::{debug, warn};
+use orientation::Orientation;
+use profiler::Profiler;
+use quirk_report::QuirkCounts;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use render::{DrawLimit, Phosphor};
+use replay::Recording;
+use throttle::Throttle;
 use timer::Timer;
 
+/// Compiled out along with the `log` dependency when the `logging` feature is off
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {};
+}
+
+pub use beep::{BeepConfig, Waveform};
+pub use cycle_costs::CycleCosts;
+pub use disasm::{disassemble, disassemble_with_coverage};
+pub use opcodes::{supported_opcodes, OpcodeInfo};
+pub use orientation::Rotation;
+pub use palette::{Palette, PixelFormat};
+pub use platform::{opcode_histogram, Platform};
+pub use quirk_report::QuirkUse;
+pub use quirks::Quirks;
+pub use replay::{InputEvent, Replay};
+pub use sandbox::{SandboxOutcome, SandboxStatus};
+pub use state::State;
+pub use step_info::{Delta, StepInfo};
+pub use timer::TimerClock;
+pub use write_log::MemoryWrite;
+
 /// Default mem size
 const DEFAULT_MEM_SIZE: usize = 4096;
 
@@ -20,22 +92,19 @@ const DEFAULT_MEM_SIZE: usize = 4096;
 const FONT_START: usize = 0x50;
 
 /// Typical program start address
-const PROGRAM_START: usize = 0x200;
+pub const PROGRAM_START: usize = 0x200;
 
-/// Display width
+/// Default display width
 pub const WIDTH: usize = 64;
 
-/// Display height
+/// Default display height
 pub const HEIGHT: usize = 32;
 
-/// RGB black
-const BLACK: u32 = 0x00_00_00;
-
-/// RGB green
-const GREEN: u32 = 0x00_ff_00;
+/// Rows in each character of `FONT`
+const FONT_CHAR_HEIGHT: usize = 5;
 
-/// Font
-const FONT: [u8; 80] = [
+/// Hex digit sprites 0 to F, `FONT_CHAR_HEIGHT` bytes each, one byte per row
+pub const FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -56,14 +125,151 @@ const FONT: [u8; 80] = [
 
 pub struct C8 {
     memory: [u8; DEFAULT_MEM_SIZE],
+    /// End of the loaded program in memory
+    program_end: usize,
+    /// Byte to fill memory above the program with on load
+    fill: Option<u8>,
     pc: usize,
     i: usize,
     reg: [u8; 16],
-    display: [[bool; HEIGHT]; WIDTH],
+    /// SUPER-CHIP user flags (`FX75`/`FX85`), which survive `soft_reset`
+    rpl: [u8; 16],
+    display: Display,
+    /// XO-CHIP planes selected for drawing and clearing
+    planes: u8,
     stack: VecDeque<usize>,
     delay: Timer,
     sound: Timer,
+    timer_clock: TimerClock,
+    /// Platform whose instruction set programs are limited to, if any
+    platform: Option<Platform>,
+    /// Whether loading a program clears the display
+    clear_on_load: bool,
+    /// Whether `jump_target` rejects bad targets rather than masking them
+    strict: bool,
+    /// What `fetch` does past the end of memory, outside strict mode
+    end_of_memory: EndOfMemory,
+    unknown_opcode: UnknownOpcodePolicy,
+    /// Whether `update_timers` and `decrement_timers` leave the timers alone
+    timers_paused: bool,
     input: [bool; 16],
+    /// Key state reported by the front-end, applied to `input` at the next frame boundary when
+    /// `snapshot_input` is set
+    pending_input: [bool; 16],
+    snapshot_input: bool,
+    /// Frames a key stays down without being pressed again, when decay is on
+    key_decay: Option<u32>,
+    /// Frames since each key was last pressed or released
+    key_age: [u32; 16],
+    input_source: Option<Box<dyn Fn() -> [bool; 16]>>,
+    quirks: Quirks,
+    palette: Palette,
+    pixel_format: PixelFormat,
+    /// Per-pixel phosphor state when fading is enabled, row-major
+    fade: Option<Vec<Phosphor>>,
+    /// Colors rendered in the previous frame when frame blending is enabled, row-major
+    previous_frame: Option<Vec<u32>>,
+    /// Display rows changed since the last `render_dirty_rows`, empty before the first call
+    dirty_rows: Vec<bool>,
+    /// Rotation and flips applied by `render`
+    orientation: Orientation,
+    /// Cap on sprite draws shown per rendered frame
+    draw_limit: Option<DrawLimit>,
+    /// Display as of the last frame boundary, shown by `render` when double buffering
+    front_buffer: Option<Vec<u8>>,
+    beep: BeepConfig,
+    /// XO-CHIP audio pattern, replacing the beep once loaded
+    audio_pattern: Option<[u8; 16]>,
+    /// XO-CHIP pitch register
+    pitch: u8,
+    audio_phase: f32,
+    halted: bool,
+    /// Register waiting to receive the next key press (`FX0A`)
+    waiting_for_key: Option<usize>,
+    /// Keys seen down since `FX0A` started waiting
+    waiting_keys_down: [bool; 16],
+    /// Keys already down when `FX0A` started, ignored until released with `fx0a_fresh_press`
+    waiting_keys_held: [bool; 16],
+    /// Cycles executed so far, one per instruction unless `cycle_costs` says otherwise
+    cycles: u64,
+    cycle_costs: CycleCosts,
+    /// Number of instructions that have touched the display
+    draws: u64,
+    /// Collision history, when tracking is on
+    collisions: Option<Collisions>,
+    /// Lit sprite pixels that fell off the edge of the display, when tracking is on
+    clipped: Option<u64>,
+    /// Cycle each pixel last changed at, row-major, when tracking is on
+    last_toggled: Option<Vec<u64>>,
+    /// Subroutine nesting, when tracking is on
+    call_depth: Option<CallDepth>,
+    /// Uses of quirk-dependent instructions, when tracking is on
+    quirk_counts: Option<QuirkCounts>,
+    /// Time spent per opcode family, when profiling is on
+    profiler: Option<Profiler>,
+    /// Addresses executed as instructions, when tracking is on
+    coverage: Option<Vec<bool>>,
+    self_modify_hook: Option<Box<dyn FnMut(usize)>>,
+    vf_operand_hook: Option<Box<dyn FnMut(usize)>>,
+    /// Writes to memory, when logging is on
+    write_log: Option<Vec<MemoryWrite>>,
+    /// Memory the program may not write to
+    protected: Vec<Range<usize>>,
+    /// Scripted key events, ordered by cycle
+    input_events: VecDeque<InputEvent>,
+    /// Cap on instructions per second for `run_for`/`run_until`
+    throttle: Option<Throttle>,
+    /// Target rate for `run_for`/`run_until`, adjusted frame by frame
+    governor: Option<Governor>,
+    /// Instructions per frame adapted to the host's speed for `run_frame`/`run_for`/`run_until`
+    frame_budget: Option<FrameBudget>,
+    rng: StdRng,
+    /// Key events recorded since `start_replay`
+    recording: Option<Recording>,
+}
+
+/// Sprite collisions seen by `draw` since tracking started
+#[derive(Default)]
+struct Collisions {
+    count: u64,
+    /// Cycle of the most recent draw that collided
+    last_cycle: Option<u64>,
+}
+
+/// What happens when the program counter runs off the end of memory without jumping
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EndOfMemory {
+    /// Fail with `C8Error::PcOutOfBounds`
+    #[default]
+    Error,
+    /// Carry on from address 0
+    Wrap,
+    /// Stop as if the program had exited
+    Halt,
+}
+
+/// What `execute` does with an opcode it doesn't recognise. Temporary, while callers move
+/// over to handling the `Result` from `try_step`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownOpcodePolicy {
+    /// Panic with the error, even from `try_step`
+    Panic,
+    /// Ignore the opcode and carry on with the next instruction
+    Skip,
+    /// Fail with `C8Error::UnknownInstruction`
+    #[default]
+    Error,
+}
+
+/// Why a run stopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepResult {
+    /// The program halted
+    Halted,
+    /// The stop condition was met
+    ConditionMet,
+    /// The instruction budget ran out first
+    BudgetExhausted,
 }
 
 trait AsU16 {
@@ -94,18 +300,164 @@ impl AsInstruction for (u8, u8) {
     }
 }
 
+/// Configures a `C8` before it's created
+pub struct C8Builder {
+    quirks: Quirks,
+    width: usize,
+    height: usize,
+    registers: [u8; 16],
+    platform: Option<Platform>,
+    timer_clock: TimerClock,
+    unknown_opcode: UnknownOpcodePolicy,
+}
+
+impl Default for C8Builder {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            width: WIDTH,
+            height: HEIGHT,
+            registers: [0; 16],
+            platform: None,
+            timer_clock: TimerClock::WallClock,
+            unknown_opcode: UnknownOpcodePolicy::default(),
+        }
+    }
+}
+
+impl C8Builder {
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Display size in pixels, 64x32 by default. Sizes are at least 1x1.
+    pub fn display_size(mut self, width: usize, height: usize) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// Target `platform`: use its usual quirks and reject instructions it doesn't define, as
+    /// `C8::set_platform` does. Later calls such as `quirks` override parts of this.
+    ///
+    /// Every platform starts with the 64x32 display and the same font, so those are unchanged.
+    /// Its clock speed is `Platform::instructions_per_frame`.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.quirks = Quirks::for_platform(platform);
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Start with V0-VF preloaded instead of zeroed
+    pub fn with_initial_registers(mut self, regs: [u8; 16]) -> Self {
+        self.registers = regs;
+        self
+    }
+
+    /// Count the timers down by the wall clock, the default, or once per `update_timers` call
+    pub fn timer_clock(mut self, clock: TimerClock) -> Self {
+        self.timer_clock = clock;
+        self
+    }
+
+    /// What to do with unknown opcodes, returning an error by default. Strict mode always errors.
+    pub fn on_unknown_opcode(mut self, policy: UnknownOpcodePolicy) -> Self {
+        self.unknown_opcode = policy;
+        self
+    }
+
+    pub fn build(self) -> C8 {
+        C8 {
+            quirks: self.quirks,
+            display: Display::new(self.width, self.height),
+            reg: self.registers,
+            platform: self.platform,
+            delay: Timer::with_clock(0, self.timer_clock),
+            sound: Timer::with_clock(0, self.timer_clock),
+            timer_clock: self.timer_clock,
+            unknown_opcode: self.unknown_opcode,
+            ..Default::default()
+        }
+    }
+}
+
+/// Map a sprite coordinate onto the display, wrapping it around the edge or clipping it
+fn edge(pos: usize, size: usize, wrap: bool) -> Option<usize> {
+    if pos < size {
+        Some(pos)
+    } else if wrap {
+        Some(pos % size)
+    } else {
+        None
+    }
+}
+
 impl Default for C8 {
     fn default() -> Self {
         let mut c8 = C8 {
             memory: [0; DEFAULT_MEM_SIZE],
+            program_end: PROGRAM_START,
+            fill: None,
             pc: PROGRAM_START,
             i: 0,
             reg: [0; 16],
-            display: [[false; HEIGHT]; WIDTH],
+            rpl: [0; 16],
+            display: Display::new(WIDTH, HEIGHT),
+            planes: 1,
             stack: VecDeque::with_capacity(1024),
             delay: Timer::zero(),
             sound: Timer::zero(),
+            timer_clock: TimerClock::WallClock,
+            platform: None,
+            clear_on_load: true,
+            strict: false,
+            end_of_memory: EndOfMemory::default(),
+            unknown_opcode: UnknownOpcodePolicy::default(),
+            timers_paused: false,
             input: [false; 16],
+            pending_input: [false; 16],
+            snapshot_input: false,
+            key_decay: None,
+            key_age: [0; 16],
+            input_source: None,
+            quirks: Quirks::default(),
+            palette: Palette::default(),
+            pixel_format: PixelFormat::default(),
+            fade: None,
+            previous_frame: None,
+            dirty_rows: Vec::new(),
+            orientation: Orientation::default(),
+            draw_limit: None,
+            front_buffer: None,
+            beep: BeepConfig::default(),
+            audio_pattern: None,
+            pitch: beep::DEFAULT_PITCH,
+            audio_phase: 0.0,
+            halted: false,
+            waiting_for_key: None,
+            waiting_keys_down: [false; 16],
+            waiting_keys_held: [false; 16],
+            cycles: 0,
+            cycle_costs: CycleCosts::default(),
+            draws: 0,
+            collisions: None,
+            clipped: None,
+            call_depth: None,
+            last_toggled: None,
+            quirk_counts: None,
+            profiler: None,
+            coverage: None,
+            self_modify_hook: None,
+            vf_operand_hook: None,
+            write_log: None,
+            protected: Vec::new(),
+            input_events: VecDeque::new(),
+            throttle: None,
+            governor: None,
+            frame_budget: None,
+            rng: StdRng::from_entropy(),
+            recording: None,
         };
 
         c8.memory[FONT_START..(FONT_START + FONT.len())].copy_from_slice(&FONT);
@@ -114,53 +466,634 @@ impl Default for C8 {
     }
 }
 
+impl fmt::Debug for C8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("C8")
+            .field("pc", &format_args!("{:#05x}", self.pc))
+            .field("i", &format_args!("{:#05x}", self.i))
+            .field("reg", &format_args!("{:02x?}", self.reg))
+            .field("stack_depth", &self.stack.len())
+            .field("delay", &self.delay.val())
+            .field("sound", &self.sound.val())
+            .field("cycles", &self.cycles)
+            .field("halted", &self.halted)
+            .field(
+                "memory",
+                &format_args!(
+                    "{} bytes, program ends at {:#05x}",
+                    self.memory.len(),
+                    self.program_end
+                ),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+/// The display as ASCII art, as from `render_ascii`
+impl fmt::Display for C8 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render_ascii())
+    }
+}
+
 impl C8 {
     pub fn new() -> Self {
         Default::default()
     }
 
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self {
+            quirks,
+            ..Default::default()
+        }
+    }
+
+    /// A machine set up for `platform`; see `C8Builder::platform`
+    pub fn for_platform(platform: Platform) -> Self {
+        C8::builder().platform(platform).build()
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Change the quirks mid-run. Each instruction reads the quirks as it executes, so the
+    /// next one sees the new set and the machine state is left as it is.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Overwrite V0-VF, e.g. to set up operands before stepping a single instruction
+    pub fn set_registers(&mut self, regs: [u8; 16]) {
+        self.reg = regs;
+    }
+
+    /// The I register
+    pub fn index(&self) -> usize {
+        self.i
+    }
+
+    /// Point I at `val`. Addresses past the end of memory are clamped to the last byte;
+    /// `ANNN` can't reach them, but `FX1E` can still carry I beyond it afterwards.
+    pub fn set_index(&mut self, val: usize) {
+        self.i = val.min(DEFAULT_MEM_SIZE - 1);
+    }
+
+    pub fn builder() -> C8Builder {
+        C8Builder::default()
+    }
+
+    /// Rows in each character of the font `FX29` points to, laid out as in `FONT`
+    pub fn font_char_height(&self) -> usize {
+        FONT_CHAR_HEIGHT
+    }
+
+    /// Display width in pixels
+    pub fn width(&self) -> usize {
+        self.display.width()
+    }
+
+    /// Display height in pixels
+    pub fn height(&self) -> usize {
+        self.display.height()
+    }
+
+    /// Load a program from a file. With the `compression` feature, gzipped files are
+    /// decompressed first.
     pub fn load_program(&mut self, path: &Path) -> Result<()> {
         let f = File::open(path)?;
         let mut b = BufReader::new(f);
         let mut buf = Vec::with_capacity(DEFAULT_MEM_SIZE);
 
         b.read_to_end(&mut buf)?;
-        self.memory[PROGRAM_START..(PROGRAM_START + buf.len())].copy_from_slice(&buf);
+        #[cfg(feature = "compression")]
+        let buf = compression::decompress(path, buf)?;
+        self.load_bytes(&buf)
+    }
+
+    pub fn load_bytes(&mut self, program: &[u8]) -> Result<()> {
+        let end = PROGRAM_START + program.len();
+        if end > DEFAULT_MEM_SIZE {
+            return Err(C8Error::ProgramTooLarge(program.len()));
+        }
+
+        self.memory[PROGRAM_START..end].copy_from_slice(program);
+        self.program_end = end;
+        if self.clear_on_load {
+            self.display.pixels_mut().fill(0);
+            self.display_replaced();
+        }
+        if let Some(byte) = self.fill {
+            self.memory[end..].fill(byte);
+        }
 
         Ok(())
     }
 
+    /// Fill the memory above the loaded program with `byte`, now and whenever a program is
+    /// loaded, so reads of memory the program never wrote are reproducible
+    pub fn fill_unused_memory(&mut self, byte: u8) {
+        self.fill = Some(byte);
+        self.memory[self.program_end..].fill(byte);
+    }
+
+    /// Whether loading a program clears the display, which it does by default. Turning this off
+    /// keeps the previous program's screen, e.g. to compare quirks on the same program.
+    pub fn set_clear_on_load(&mut self, clear: bool) {
+        self.clear_on_load = clear;
+    }
+
+    /// Restart the loaded program, clearing the registers, stack, timers and display but keeping
+    /// memory and the RPL user flags, like a reset on SUPER-CHIP hardware
+    pub fn soft_reset(&mut self) {
+        self.pc = PROGRAM_START;
+        self.i = 0;
+        self.reg = [0; 16];
+        self.stack.clear();
+        self.delay.set(0);
+        self.sound.set(0);
+        self.planes = 1;
+        self.display.pixels_mut().fill(0);
+        self.display_replaced();
+        self.halted = false;
+        self.waiting_for_key = None;
+        self.waiting_keys_down = [false; 16];
+        self.waiting_keys_held = [false; 16];
+        self.audio_pattern = None;
+        self.pitch = beep::DEFAULT_PITCH;
+    }
+
+    /// Reject instructions the platform doesn't define with `C8Error::IllegalForPlatform`, to
+    /// catch programs run on the wrong target. With `None`, the default, every instruction the
+    /// interpreter knows is allowed.
+    pub fn set_platform(&mut self, platform: Option<Platform>) {
+        self.platform = platform;
+    }
+
+    /// Treat jumps, calls and returns to odd addresses or past the end of memory as errors
+    /// rather than following them, and reject `5XYN`/`9XYN` unless N is 0
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Charge instructions different numbers of cycles, to model the original hardware's
+    /// timing
+    pub fn set_cycle_costs(&mut self, costs: CycleCosts) {
+        self.cycle_costs = costs;
+    }
+
+    /// Choose what happens when the program counter runs off the end of memory without a jump,
+    /// which is an error by default. Strict mode ignores this and always errors.
+    pub fn set_end_of_memory(&mut self, behaviour: EndOfMemory) {
+        self.end_of_memory = behaviour;
+    }
+
+    /// Seed the random number generator used by `CXNN`, making runs reproducible
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
     pub fn tick(&mut self) {
-        self.delay.update();
-        self.sound.update();
+        self.update_timers();
+        self.step();
+    }
+
+    /// Fetch and execute a single instruction without touching the timers.
+    ///
+    /// Panics if the instruction can't be executed; see `try_step` for a fallible version.
+    pub fn step(&mut self) {
+        if let Err(e) = self.try_step() {
+            panic!("{e}");
+        }
+    }
+
+    /// Like `step`, but return an error rather than panicking if the instruction can't be
+    /// executed. No program can make this panic unless unknown opcodes are set to
+    /// `UnknownOpcodePolicy::Panic`.
+    pub fn try_step(&mut self) -> Result<()> {
+        if self.halted {
+            return Ok(());
+        }
+
+        while let Some(event) = self.input_events.front().filter(|e| e.cycle <= self.cycles) {
+            let (key, pressed) = (event.key, event.pressed);
+            self.input_events.pop_front();
+            self.key_pressed(key, pressed);
+        }
+
+        // execution is paused while waiting for a key, but the timers keep running
+        let family = if let Some(x) = self.waiting_for_key {
+            self.get_key(x);
+            0xf
+        } else {
+            if self.pc >= DEFAULT_MEM_SIZE - 1 && self.end_of_memory() == EndOfMemory::Halt {
+                self.halted = true;
+                return Ok(());
+            }
+            let at = self.pc;
+            let instruction = self.fetch()?;
+            self.note_executed(at);
+            self.check_platform(instruction)?;
+            let start = self.profiler.is_some().then(Instant::now);
+            #[cfg(not(feature = "dispatch-table"))]
+            self.execute(instruction)?;
+            #[cfg(feature = "dispatch-table")]
+            dispatch::execute(self, instruction)?;
+            if let (Some(profiler), Some(start)) = (&mut self.profiler, start) {
+                profiler.record(instruction.0, start);
+            }
+            instruction.0
+        };
+        self.cycles += self.cycle_costs.cost(family);
+
+        Ok(())
+    }
+
+    /// Count sprite draws that collide with lit pixels, for `collision_count` and
+    /// `last_collision_cycle`. Turning tracking on or off resets the history.
+    pub fn set_collision_tracking(&mut self, enabled: bool) {
+        self.collisions = enabled.then(Collisions::default);
+    }
+
+    /// Number of draws that set VF since tracking was turned on or last reset
+    pub fn collision_count(&self) -> u64 {
+        self.collisions.as_ref().map_or(0, |c| c.count)
+    }
+
+    /// Cycle of the last draw that set VF, if any since tracking was turned on or last reset
+    pub fn last_collision_cycle(&self) -> Option<u64> {
+        self.collisions.as_ref().and_then(|c| c.last_cycle)
+    }
+
+    /// Forget the collisions seen so far, keeping tracking on if it was
+    pub fn reset_collisions(&mut self) {
+        if let Some(collisions) = &mut self.collisions {
+            *collisions = Collisions::default();
+        }
+    }
+
+    /// Call `hook` with the address of any `8XY4` to `8XYE` instruction that uses VF as X or Y.
+    /// Those instructions also set VF as a flag, so this is almost always a bug. The flag wins
+    /// when VF is the destination. Shifts only count Y with the `shift_vy` quirk, since they
+    /// ignore it otherwise. Each use is logged as a warning too.
+    pub fn set_vf_operand_hook(&mut self, hook: Option<Box<dyn FnMut(usize)>>) {
+        self.vf_operand_hook = hook;
+    }
+
+    /// Count the lit pixels of sprites that are clipped at the edges of the display, for
+    /// `clipped_pixels`. Turning tracking on or off resets the count.
+    pub fn set_clip_tracking(&mut self, enabled: bool) {
+        self.clipped = enabled.then_some(0);
+    }
+
+    /// Sprite pixels clipped since tracking was turned on or last reset, e.g. once a frame. A
+    /// pixel drawn to both planes counts twice. Wrapping edges never clip.
+    pub fn clipped_pixels(&self) -> u64 {
+        self.clipped.unwrap_or(0)
+    }
+
+    /// Zero the clipped pixel count, keeping tracking on if it was
+    pub fn reset_clipped_pixels(&mut self) {
+        if let Some(clipped) = &mut self.clipped {
+            *clipped = 0;
+        }
+    }
+
+    /// Record the cycle each pixel last changed at, for `last_toggled`. This costs a `u64` per
+    /// pixel and a write for every pixel drawn, so it's off by default. Turning it on or off
+    /// resets the history.
+    pub fn set_pixel_history(&mut self, enabled: bool) {
+        self.last_toggled = enabled.then(|| vec![0; self.display.pixels().len()]);
+    }
+
+    /// Cycle of the instruction that last changed each pixel, row-major, or 0 for pixels that
+    /// haven't changed. `None` unless `set_pixel_history` is on.
+    pub fn last_toggled(&self) -> Option<&[u64]> {
+        self.last_toggled.as_deref()
+    }
+
+    /// Whether execution is paused on `FX0A` until a key is pressed
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key.is_some()
+    }
+
+    /// Run one 60Hz frame the way a front-end would: execute `instructions_per_frame`
+    /// instructions, count the timers down by a single tick, then render into `frame`.
+    ///
+    /// Stops executing early if the program halts, but still counts the timers down and renders.
+    pub fn run_frame(&mut self, instructions_per_frame: usize, frame: &mut [u32]) -> Result<()> {
+        let instructions = match &self.frame_budget {
+            Some(frame_budget) => frame_budget.instructions(),
+            None => instructions_per_frame,
+        };
+        let start = Instant::now();
+        let mut executed = 0;
+        while executed < instructions && !self.halted {
+            self.try_step()?;
+            executed += 1;
+        }
+        self.decrement_timers();
+        self.render(frame);
+        if let Some(frame_budget) = &mut self.frame_budget {
+            frame_budget.record(executed, start.elapsed());
+        }
+        Ok(())
+    }
+
+    /// Step until the program halts or `budget` cycles have run
+    pub fn run_for(&mut self, budget: usize) -> Result<StepResult> {
+        self.run_until(budget, |_| false)
+    }
+
+    /// Step until `done` returns true, the program halts, or `budget` cycles have run. An
+    /// instruction that can't be executed stops the run with its error.
+    pub fn run_until(
+        &mut self,
+        budget: usize,
+        done: impl FnMut(&C8) -> bool,
+    ) -> Result<StepResult> {
+        let budget = match &mut self.governor {
+            Some(governor) => budget.min(governor.due()),
+            None => budget,
+        };
+        let max_instructions = self
+            .frame_budget
+            .as_ref()
+            .map_or(usize::MAX, FrameBudget::instructions);
+        let start = Instant::now();
+        let mut executed = 0;
+        let result = self.run_budget(budget, max_instructions, &mut executed, done);
+        if let Some(frame_budget) = &mut self.frame_budget {
+            frame_budget.record(executed, start.elapsed());
+        }
+        result
+    }
+
+    /// `run_until` once the budget has been settled, stopping after `budget` cycles or
+    /// `max_instructions` instructions and counting the instructions run in `executed`
+    fn run_budget(
+        &mut self,
+        budget: usize,
+        max_instructions: usize,
+        executed: &mut usize,
+        mut done: impl FnMut(&C8) -> bool,
+    ) -> Result<StepResult> {
+        let end = self.cycles.saturating_add(budget as u64);
+        while self.cycles < end && *executed < max_instructions {
+            if self.halted {
+                return Ok(StepResult::Halted);
+            }
+            self.try_step()?;
+            *executed += 1;
+            if let Some(throttle) = &mut self.throttle {
+                throttle.wait();
+            }
+            if done(self) {
+                return Ok(StepResult::ConditionMet);
+            }
+        }
+
+        if self.halted {
+            Ok(StepResult::Halted)
+        } else {
+            Ok(StepResult::BudgetExhausted)
+        }
+    }
+
+    /// Treat each call to `run_for` or `run_until` as a frame and run however many instructions
+    /// keep the average rate at `ips`, measuring the time between calls. The budget passed in
+    /// still caps each frame. Pass 0 to go back to running the whole budget.
+    ///
+    /// This replaces a fixed number of instructions per frame: it holds the speed steady when
+    /// the frame rate varies, where a fixed count runs faster or slower along with it.
+    ///
+    /// The frame's budget is counted in cycles, so with `set_cycle_costs` the target is really
+    /// cycles per second.
+    pub fn set_target_ips(&mut self, ips: u32) {
+        self.governor = (ips > 0).then(|| Governor::new(ips));
+    }
 
-        let instruction = self.fetch();
-        self.execute(instruction);
+    /// Adapt the instructions each `run_frame`, `run_for` or `run_until` call runs so the call
+    /// takes about `target_ms` milliseconds, e.g. 16 to fill a 60Hz frame. The count grows while
+    /// calls finish early and shrinks when they overrun, so the program runs as fast as the host
+    /// allows while the front-end stays responsive. `run_frame` uses it in place of
+    /// `instructions_per_frame`, and the others are capped by it. Pass 0 to turn it off.
+    ///
+    /// Unlike `set_target_ips`, which holds the program at a fixed speed, the speed here depends
+    /// on the host, so this suits running as fast as smoothly possible rather than playing games
+    /// at their intended speed. `set_max_ips` can still cap it.
+    pub fn set_adaptive_frame_budget(&mut self, target_ms: u32) {
+        self.frame_budget =
+            (target_ms > 0).then(|| FrameBudget::new(Duration::from_millis(target_ms.into())));
     }
 
-    pub fn render(&mut self, frame: &mut [u32]) {
-        for (i, pixel) in frame.iter_mut().enumerate() {
-            let c = i % WIDTH;
-            let r = i / WIDTH;
+    /// Instructions the adaptive frame budget currently allows per frame, if it's on
+    pub fn adaptive_frame_budget(&self) -> Option<usize> {
+        self.frame_budget.as_ref().map(FrameBudget::instructions)
+    }
+
+    /// Limit `run_for` and `run_until` to at most `ips` instructions per second, sleeping as
+    /// needed. This only ever slows execution down, so the lower of the cap and the rate the
+    /// front-end drives the interpreter at wins. Pass 0 to remove the cap.
+    pub fn set_max_ips(&mut self, ips: u32) {
+        self.throttle = (ips > 0).then(|| Throttle::new(ips));
+    }
 
-            *pixel = if self.display[c][r] { GREEN } else { BLACK };
+    /// Step until an instruction draws to or clears the display, the program halts, or `cap`
+    /// instructions have run. Returns the number of instructions executed, or the error from an
+    /// instruction that can't be executed.
+    pub fn run_until_first_draw(&mut self, cap: usize) -> Result<usize> {
+        for n in 0..cap {
+            let draws = self.draws;
+            self.try_step()?;
+            if self.draws != draws || self.halted {
+                return Ok(n + 1);
+            }
         }
+        Ok(cap)
     }
 
+    /// Number of cycles executed so far, which is the number of instructions unless cycle costs
+    /// are set
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Schedule a key event to be applied just before the instruction at `at_cycle` executes
+    pub fn queue_input_event(&mut self, at_cycle: u64, key: usize, pressed: bool) {
+        let idx = self.input_events.partition_point(|e| e.cycle <= at_cycle);
+        self.input_events.insert(
+            idx,
+            InputEvent {
+                cycle: at_cycle,
+                key,
+                pressed,
+            },
+        );
+    }
+
+    /// Advance the timers according to the wall clock, or by a single tick with
+    /// `TimerClock::Ticks`
+    pub fn update_timers(&mut self) {
+        if !self.timers_paused {
+            self.delay.update();
+            self.sound.update();
+        }
+        self.frame_boundary();
+    }
+
+    /// Freeze the delay and sound timers so neither `update_timers` nor `decrement_timers`
+    /// counts them down, e.g. while stepping through a program in a debugger.
+    ///
+    /// Time spent paused isn't caught up on afterwards.
+    pub fn pause_timers(&mut self, paused: bool) {
+        if self.timers_paused && !paused {
+            self.delay.resume();
+            self.sound.resume();
+        }
+        self.timers_paused = paused;
+    }
+
+    /// Count the timers down by a single tick, independent of the wall clock
+    pub fn decrement_timers(&mut self) {
+        if !self.timers_paused {
+            self.delay.decrement();
+            self.sound.decrement();
+        }
+        self.frame_boundary();
+    }
+
+    /// Work done once a frame, by `update_timers` or `decrement_timers`, besides the timers:
+    /// take the key state from the input source or snapshot, swap the display buffers and age
+    /// held keys
+    fn frame_boundary(&mut self) {
+        if let Some(source) = &self.input_source {
+            self.pending_input = source();
+        }
+        if self.snapshot_input || self.input_source.is_some() {
+            self.input = self.pending_input;
+        }
+        self.swap_buffers();
+        self.decay_keys();
+    }
+
+    /// Whether the program has stopped, e.g. by executing `00FD`
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Fill `samples` with the tone set by `set_beep`, or the XO-CHIP audio pattern once one is
+    /// loaded, while the sound timer is active, or silence otherwise.
+    ///
+    /// The waveform phase carries over between calls so consecutive buffers join up cleanly.
+    pub fn sound_active_samples(&mut self, sample_rate: u32, samples: &mut [f32]) {
+        if self.sound.val() == 0 {
+            samples.fill(0.0);
+            self.audio_phase = 0.0;
+            return;
+        }
+
+        let step = self.audio_step(sample_rate);
+        for sample in samples.iter_mut() {
+            *sample = self.audio_sample(self.audio_phase);
+            self.audio_phase = (self.audio_phase + step).fract();
+        }
+    }
+
+    /// How far the sound timer is through its current tick as of the last `update_timers`, from
+    /// 0 up to but not including 1, for lining audio up with the timer
+    pub fn sound_fraction(&self) -> f32 {
+        self.sound.fraction()
+    }
+
+    /// Press or release a key. Keys past `0xf` don't exist on the keypad and are ignored.
     pub fn key_pressed(&mut self, key: usize, pressed: bool) {
         debug!("key {key:x} => {pressed}");
-        self.input[key] = pressed;
+        if key > 0xf {
+            return;
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.record(InputEvent {
+                cycle: self.cycles,
+                key,
+                pressed,
+            });
+        }
+        self.pending_input[key] = pressed;
+        if !self.snapshot_input {
+            self.input[key] = pressed;
+        }
+        self.key_age[key] = 0;
+    }
+
+    /// Release every key that's down, e.g. when the window loses focus and release events may
+    /// be missed
+    pub fn release_all_keys(&mut self) {
+        for key in 0..16 {
+            if self.pending_input[key] {
+                self.key_pressed(key, false);
+            }
+        }
+    }
+
+    /// Release keys that haven't been pressed again for `frames` frame boundaries, i.e. calls to
+    /// `update_timers` or `decrement_timers`, so a missed release can't leave a key stuck down.
+    /// Front-ends using this should repeat `key_pressed` for held keys every frame.
+    pub fn set_key_decay(&mut self, frames: Option<u32>) {
+        self.key_decay = frames;
+        self.key_age = [0; 16];
+    }
+
+    /// Age the keys that are down by a frame, releasing any that have decayed
+    fn decay_keys(&mut self) {
+        let Some(frames) = self.key_decay else {
+            return;
+        };
+        for key in 0..16 {
+            if self.pending_input[key] {
+                self.key_age[key] += 1;
+                if self.key_age[key] >= frames {
+                    self.key_pressed(key, false);
+                }
+            }
+        }
+    }
+
+    /// Only apply key changes at frame boundaries, i.e. when `update_timers` or
+    /// `decrement_timers` is called.
+    ///
+    /// Every instruction within a frame then sees the same key state, which avoids glitches in
+    /// tight input loops, at the cost of up to a frame of extra input latency.
+    pub fn set_input_snapshot(&mut self, enabled: bool) {
+        self.snapshot_input = enabled;
+        if !enabled {
+            self.input = self.pending_input;
+        }
+    }
+
+    /// Query `source` for the key state at every frame boundary (`update_timers` or
+    /// `decrement_timers`).
+    ///
+    /// `key_pressed` keeps working alongside a source, but the source takes precedence: its state
+    /// replaces any keys set through `key_pressed` at the next frame boundary.
+    pub fn set_input_source(&mut self, source: Box<dyn Fn() -> [bool; 16]>) {
+        self.input_source = Some(source);
     }
 
-    fn execute(&mut self, instruction: Instruction) {
+    fn execute(&mut self, instruction: Instruction) -> Result<()> {
         match instruction {
+            // not a real instruction, but zeros are common padding so run through them
+            (0x0, 0x0, 0x0, 0x0) => {}
             (0x0, 0x0, 0xe, 0x0) => self.clear_screen(),
-            (0x0, 0x0, 0xe, 0xe) => self.ret(),
-            (0x1, a, b, c) => self.jump((a, b, c).as_u16() as usize),
-            (0x2, a, b, c) => self.sub((a, b, c).as_u16() as usize),
+            (0x0, 0x0, 0xe, 0xe) => self.ret()?,
+            (0x0, 0x0, 0xf, 0xd) => self.exit(),
+            (0x1, a, b, c) => self.jump((a, b, c).as_u16() as usize)?,
+            (0x2, a, b, c) => self.sub((a, b, c).as_u16() as usize)?,
             (0x3, x, a, b) => self.skip_if(self.reg[x as usize] as u16 == (a, b).as_u16()),
             (0x4, x, a, b) => self.skip_if(self.reg[x as usize] as u16 != (a, b).as_u16()),
-            (0x5, x, y, _) => self.skip_if(self.reg[x as usize] == self.reg[y as usize]),
+            (0x5, x, y, 0) => self.skip_if(self.reg[x as usize] == self.reg[y as usize]),
+            (0x5, x, y, 2) => self.dump_range(x as usize, y as usize)?,
+            (0x5, x, y, 3) => self.load_range(x as usize, y as usize),
             (0x6, x, a, b) => self.set_reg(x as usize, (a, b).as_u16()),
             (0x7, x, a, b) => self.add_to_reg(x as usize, (a, b).as_u16()),
             (0x8, x, y, 0) => self.assign(x as usize, y as usize),
@@ -169,106 +1102,257 @@ impl C8 {
             (0x8, x, y, 3) => self.xor(x as usize, y as usize),
             (0x8, x, y, 4) => self.plus(x as usize, y as usize),
             (0x8, x, y, 5) => self.minus(x as usize, y as usize),
-            (0x8, x, _, 6) => self.shr(x as usize),
+            (0x8, x, y, 6) => self.shr(x as usize, y as usize),
             (0x8, x, y, 7) => self.diff(x as usize, y as usize),
-            (0x8, x, _, 0xe) => self.shl(x as usize),
-            (0x9, x, y, _) => self.skip_if(self.reg[x as usize] != self.reg[y as usize]),
+            (0x8, x, y, 0xe) => self.shl(x as usize, y as usize),
+            (0x9, x, y, 0) => self.skip_if(self.reg[x as usize] != self.reg[y as usize]),
             (0xa, a, b, c) => self.set_index((a, b, c).as_u16() as usize),
+            (0xb, a, b, c) => self.jump_offset((a, b, c).as_u16() as usize)?,
             (0xc, x, a, b) => self.and_rand(x as usize, (a, b).as_u16()),
             (0xd, x, y, n) => self.draw(x as usize, y as usize, n),
-            (0xe, x, 0x9, 0xe) => self.skip_if(self.input[self.reg[x as usize] as usize]),
-            (0xe, x, 0xa, 0x1) => self.skip_if(!self.input[self.reg[x as usize] as usize]),
+            (0xe, x, 0x9, 0xe) => self.skip_if(self.input[self.reg[x as usize] as usize & 0xf]),
+            (0xe, x, 0xa, 0x1) => self.skip_if(!self.input[self.reg[x as usize] as usize & 0xf]),
+            (0xf, n, 0x0, 0x1) => self.select_planes(n),
+            (0xf, 0x0, 0x0, 0x2) => self.load_audio_pattern(),
+            (0xf, x, 0x3, 0xa) => self.set_pitch(x as usize),
             (0xf, x, 0x0, 0xa) => self.get_key(x as usize),
-            (0xf, x, 0x3, 0x3) => self.bcd(x as usize),
-            (0xf, x, 0x5, 0x5) => self.dump(x as usize),
+            (0xf, x, 0x3, 0x3) => self.bcd(x as usize)?,
+            (0xf, x, 0x5, 0x5) => self.dump(x as usize)?,
             (0xf, x, 0x6, 0x5) => self.load(x as usize),
+            (0xf, x, 0x7, 0x5) => self.save_rpl(x as usize),
+            (0xf, x, 0x8, 0x5) => self.load_rpl(x as usize),
             (0xf, x, 0x2, 0x9) => self.char(x as usize),
             (0xf, x, 0x1, 0x5) => self.delay(x as usize),
             (0xf, x, 0x1, 0x8) => self.sound(x as usize),
             (0xf, x, 0x0, 0x7) => self.get_delay(x as usize),
             (0xf, x, 0x1, 0xe) => self.add_to_index(x as usize),
-            _ => panic!(
-                "Unknown instruction {:X}{:X}{:X}{:X}",
-                instruction.0, instruction.1, instruction.2, instruction.3
-            ),
+            // the low nibble should be 0, but many interpreters ignore it
+            (0x5, x, y, _) if !self.strict => {
+                self.skip_if(self.reg[x as usize] == self.reg[y as usize])
+            }
+            (0x9, x, y, _) if !self.strict => {
+                self.skip_if(self.reg[x as usize] != self.reg[y as usize])
+            }
+            (a, b, c, d) => {
+                let err = C8Error::UnknownInstruction((a as u16) << 12 | (b, c, d).as_u16());
+                match self.unknown_opcode() {
+                    // pc is already past the opcode
+                    UnknownOpcodePolicy::Skip => {}
+                    UnknownOpcodePolicy::Error => return Err(err),
+                    UnknownOpcodePolicy::Panic => panic!("{err}"),
+                }
+            }
         }
+
+        Ok(())
     }
 
-    fn fetch(&mut self) -> Instruction {
+    fn fetch(&mut self) -> Result<Instruction> {
+        if self.end_of_memory() == EndOfMemory::Wrap {
+            // an instruction straddling the end takes its second byte from address 0
+            self.pc %= DEFAULT_MEM_SIZE;
+            let lo = self.memory[(self.pc + 1) % DEFAULT_MEM_SIZE];
+            let instruction = (self.memory[self.pc], lo).as_instruction();
+            self.pc = (self.pc + 2) % DEFAULT_MEM_SIZE;
+            debug!("{instruction:?}");
+            return Ok(instruction);
+        }
+        if self.pc >= DEFAULT_MEM_SIZE - 1 {
+            return Err(C8Error::PcOutOfBounds(self.pc));
+        }
+
         let instruction = (self.memory[self.pc], self.memory[self.pc + 1]).as_instruction();
         self.pc += 2;
         debug!("{instruction:?}");
-        instruction
+        Ok(instruction)
     }
 
     fn clear_screen(&mut self) {
-        for col in self.display.iter_mut() {
-            for pixel in col.iter_mut() {
-                *pixel = false;
+        self.draws += 1;
+        for (i, pixel) in self.display.pixels_mut().iter_mut().enumerate() {
+            if *pixel & self.planes != 0 {
+                if let Some(toggled) = &mut self.last_toggled {
+                    toggled[i] = self.cycles;
+                }
             }
+            *pixel &= !self.planes;
         }
+        self.dirty_rows.fill(true);
+        self.limit_draw();
     }
 
-    fn jump(&mut self, to: usize) {
-        self.pc = to;
+    fn jump(&mut self, to: usize) -> Result<()> {
+        self.pc = self.jump_target(to)?;
+        Ok(())
+    }
+
+    fn jump_offset(&mut self, to: usize) -> Result<()> {
+        self.jump(to + self.reg[0] as usize)
+    }
+
+    fn check_platform(&self, (a, b, c, d): Instruction) -> Result<()> {
+        let opcode = (a as u16) << 12 | (b, c, d).as_u16();
+        match self.platform {
+            Some(platform) if !platform.allows(opcode) => {
+                Err(C8Error::IllegalForPlatform { opcode, platform })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Strict mode always treats unknown opcodes as an error
+    fn unknown_opcode(&self) -> UnknownOpcodePolicy {
+        if self.strict {
+            UnknownOpcodePolicy::Error
+        } else {
+            self.unknown_opcode
+        }
+    }
+
+    /// Strict mode always treats running off the end of memory as an error
+    fn end_of_memory(&self) -> EndOfMemory {
+        if self.strict {
+            EndOfMemory::Error
+        } else {
+            self.end_of_memory
+        }
+    }
+
+    /// Where a jump to `addr` lands. Targets past the end of memory wrap to the 12-bit address
+    /// space as on the original interpreter, but in strict mode they're an error, as are odd
+    /// targets.
+    fn jump_target(&self, addr: usize) -> Result<usize> {
+        if self.strict && (!addr.is_multiple_of(2) || addr + 1 >= DEFAULT_MEM_SIZE) {
+            return Err(C8Error::MisalignedJump { addr });
+        }
+        Ok(addr & (DEFAULT_MEM_SIZE - 1))
     }
 
     fn set_reg(&mut self, x: usize, val: u16) {
         self.reg[x] = val as u8;
     }
 
+    /// `7XNN` wraps on overflow and never touches VF, even when X is F; only `8XY4` reports a
+    /// carry
     fn add_to_reg(&mut self, x: usize, val: u16) {
         self.reg[x] = self.reg[x].wrapping_add(val as u8);
     }
 
-    fn set_index(&mut self, val: usize) {
-        self.i = val;
-    }
-
     fn add_to_index(&mut self, x: usize) {
-        self.i += self.reg[x] as usize;
+        // I is 16 bits wide; memory accesses wrap at a divisor of that anyway
+        self.i = (self.i + self.reg[x] as usize) & 0xffff;
     }
 
+    /// Draw an N-row sprite from I at (VX, VY), setting VF if any lit pixel was turned off.
+    ///
+    /// `DXY0` is a 16x16 sprite stored as two bytes per row with the `draw_16x16` quirk.
+    /// Without it, `DXY0` is a no-op apart from clearing VF: nothing is drawn and it doesn't
+    /// count towards the draw limit.
     fn draw(&mut self, x: usize, y: usize, height: u8) {
+        if height == 0 {
+            self.note_quirk("DXY0", "draw_16x16", self.quirks.draw_16x16);
+            if !self.quirks.draw_16x16 {
+                self.reg[0xf] = 0;
+                return;
+            }
+        }
+
+        self.draws += 1;
         self.reg[0xf] = 0;
 
-        let vx = self.reg[x] as usize % WIDTH;
-        let vy = self.reg[y] as usize % HEIGHT;
+        let (w, h) = (self.display.width(), self.display.height());
+        let vx = self.reg[x] as usize % w;
+        let vy = self.reg[y] as usize % h;
+
+        let (rows, cols) = match height {
+            0 => (16, 16),
+            n => (n as usize, 8),
+        };
+        let row_bytes = cols / 8;
+
+        if vx + cols > w {
+            self.note_quirk("DXYN", "wrap_x", self.quirks.wrap_x);
+        }
+        if vy + rows > h {
+            self.note_quirk("DXYN", "wrap_y", self.quirks.wrap_y);
+        }
+
+        // each selected plane takes its own sprite, stored one after another starting at `i`
+        let mut addr = self.i;
+        for plane in [0b01, 0b10] {
+            if self.planes & plane == 0 {
+                continue;
+            }
 
-        for r in 0..height as usize {
-            let row = self.memory[self.i + r];
-            for c in 0..8 {
-                if ((row << c) & 0b10000000) > 0 {
-                    if let Some(col) = self.display.get_mut(vx + c) {
-                        if let Some(p) = col.get_mut(vy + r) {
-                            *p ^= true;
+            for r in 0..rows {
+                // sprite data past the end of memory wraps around to the start
+                let byte = |n: usize| self.memory[(addr + r * row_bytes + n) % DEFAULT_MEM_SIZE];
+                let row = match row_bytes {
+                    1 => u16::from(byte(0)) << 8,
+                    _ => u16::from_be_bytes([byte(0), byte(1)]),
+                };
+                for c in 0..cols {
+                    if ((row << c) & 0x8000) > 0 {
+                        let px = edge(vx + c, w, self.quirks.wrap_x);
+                        let py = edge(vy + r, h, self.quirks.wrap_y);
+                        if let (Some(px), Some(py)) = (px, py) {
+                            let p = self.display.get_mut(px, py);
+                            *p ^= plane;
 
-                            if !*p {
+                            if *p & plane == 0 {
                                 self.reg[0xf] = 1;
                             }
+                            if let Some(toggled) = &mut self.last_toggled {
+                                toggled[py * w + px] = self.cycles;
+                            }
+                            if let Some(dirty) = self.dirty_rows.get_mut(py) {
+                                *dirty = true;
+                            }
+                        } else if let Some(clipped) = &mut self.clipped {
+                            *clipped += 1;
                         }
                     }
                 }
             }
+            addr += rows * row_bytes;
         }
+
+        if self.reg[0xf] == 1 {
+            if let Some(collisions) = &mut self.collisions {
+                collisions.count += 1;
+                collisions.last_cycle = Some(self.cycles);
+            }
+        }
+        self.limit_draw();
+    }
+
+    fn select_planes(&mut self, n: u8) {
+        self.planes = n & 0b11;
     }
 
     fn skip_if(&mut self, skip: bool) {
         if skip {
-            self.fetch();
+            self.pc += 2;
         }
     }
 
-    fn sub(&mut self, at: usize) {
+    fn sub(&mut self, at: usize) -> Result<()> {
+        let at = self.jump_target(at)?;
         self.stack.push_front(self.pc);
         self.pc = at;
+        self.note_call(self.stack.len());
+        Ok(())
+    }
+
+    fn ret(&mut self) -> Result<()> {
+        let to = self.jump_target(*self.stack.front().ok_or(C8Error::StackUnderflow)?)?;
+        self.stack.pop_front();
+        self.pc = to;
+        Ok(())
     }
 
-    fn ret(&mut self) {
-        self.pc = self
-            .stack
-            .pop_front()
-            .expect("Stack shouldn't be empty when `ret()` is called");
+    fn exit(&mut self) {
+        self.halted = true;
     }
 
     fn assign(&mut self, x: usize, y: usize) {
@@ -287,43 +1371,105 @@ impl C8 {
         self.reg[x] ^= self.reg[y];
     }
 
+    /// Call and log the VF operand hook if a flag-setting instruction uses VF as X or Y. These
+    /// instructions read their operands first and write the flag last, so VF holds the flag even
+    /// when it's the destination.
+    fn check_vf_operand(&mut self, x: usize, y: usize) {
+        if x != 0xf && y != 0xf {
+            return;
+        }
+        if let Some(hook) = &mut self.vf_operand_hook {
+            let at = self.pc.wrapping_sub(2) % DEFAULT_MEM_SIZE;
+            warn!("instruction at {at:#05x} uses VF as an operand and sets the flag");
+            hook(at);
+        }
+    }
+
     fn plus(&mut self, x: usize, y: usize) {
+        self.check_vf_operand(x, y);
         let (sum, overflow) = self.reg[x].overflowing_add(self.reg[y]);
         self.reg[x] = sum;
         self.reg[0xf] = if overflow { 1 } else { 0 };
     }
 
     fn minus(&mut self, x: usize, y: usize) {
+        self.check_vf_operand(x, y);
         let (diff, underflow) = self.reg[x].overflowing_sub(self.reg[y]);
         self.reg[x] = diff;
         self.reg[0xf] = if !underflow { 1 } else { 0 };
     }
 
-    fn shr(&mut self, x: usize) {
-        self.reg[0xf] = if self.reg[x] & 0b00000001 > 0 { 1 } else { 0 };
-        self.reg[x] >>= 1;
+    fn shr(&mut self, x: usize, y: usize) {
+        self.note_quirk("8XY6", "shift_vy", self.quirks.shift_vy);
+        let val = self.shift_operand(x, y);
+        self.reg[x] = val >> 1;
+        self.reg[0xf] = val & 0b00000001;
     }
 
     fn diff(&mut self, x: usize, y: usize) {
+        self.check_vf_operand(x, y);
         let (diff, underflow) = self.reg[y].overflowing_sub(self.reg[x]);
         self.reg[x] = diff;
         self.reg[0xf] = if !underflow { 1 } else { 0 };
     }
 
-    fn shl(&mut self, x: usize) {
-        self.reg[0xf] = if self.reg[x] & 0b10000000 > 0 { 1 } else { 0 };
-        self.reg[x] <<= 1;
+    fn shl(&mut self, x: usize, y: usize) {
+        self.note_quirk("8XYE", "shift_vy", self.quirks.shift_vy);
+        let val = self.shift_operand(x, y);
+        self.reg[x] = val << 1;
+        self.reg[0xf] = val >> 7;
+    }
+
+    /// The value `8XY6` and `8XYE` shift: VY with the `shift_vy` quirk, otherwise VX
+    fn shift_operand(&mut self, x: usize, y: usize) -> u8 {
+        let src = if self.quirks.shift_vy { y } else { x };
+        self.check_vf_operand(x, src);
+        self.reg[src]
     }
 
-    fn dump(&mut self, x: usize) {
-        self.memory[self.i..=(self.i + x)].copy_from_slice(&self.reg[0..=x])
+    fn dump(&mut self, x: usize) -> Result<()> {
+        // registers past the end of memory wrap around to the start
+        for n in 0..=x {
+            self.write_memory((self.i + n) % DEFAULT_MEM_SIZE, self.reg[n])?;
+        }
+        Ok(())
     }
 
     fn load(&mut self, x: usize) {
-        self.reg[0..=x].copy_from_slice(&self.memory[self.i..=(self.i + x)]);
+        // as with `dump`, reads past the end of memory wrap around to the start
+        for n in 0..=x {
+            self.reg[n] = self.memory[(self.i + n) % DEFAULT_MEM_SIZE];
+        }
+    }
+
+    /// Registers X to Y in order, counting down if X is past Y
+    fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+        let (lo, hi) = (x.min(y), x.max(y));
+        (lo..=hi).map(move |n| if x <= y { n } else { x + y - n })
     }
 
-    fn bcd(&mut self, x: usize) {
+    fn dump_range(&mut self, x: usize, y: usize) -> Result<()> {
+        for (n, r) in C8::register_range(x, y).enumerate() {
+            self.write_memory((self.i + n) % DEFAULT_MEM_SIZE, self.reg[r])?;
+        }
+        Ok(())
+    }
+
+    fn load_range(&mut self, x: usize, y: usize) {
+        for (n, r) in C8::register_range(x, y).enumerate() {
+            self.reg[r] = self.memory[(self.i + n) % DEFAULT_MEM_SIZE];
+        }
+    }
+
+    fn save_rpl(&mut self, x: usize) {
+        self.rpl[..=x].copy_from_slice(&self.reg[..=x]);
+    }
+
+    fn load_rpl(&mut self, x: usize) {
+        self.reg[..=x].copy_from_slice(&self.rpl[..=x]);
+    }
+
+    fn bcd(&mut self, x: usize) -> Result<()> {
         let mut vx = self.reg[x];
         let mut digits = [0u8; 3];
 
@@ -332,15 +1478,19 @@ impl C8 {
         digits[1] = vx / 10;
         vx %= 10;
         digits[2] = vx;
-        self.memory[self.i..(self.i + 3)].copy_from_slice(&digits);
+        // digits past the end of memory wrap around to the start
+        for (n, d) in digits.iter().enumerate() {
+            self.write_memory((self.i + n) % DEFAULT_MEM_SIZE, *d)?;
+        }
+        Ok(())
     }
 
     fn char(&mut self, x: usize) {
-        self.i = FONT_START + (self.reg[x] as usize * 5); // each char is 5 bytes
+        self.i = FONT_START + self.reg[x] as usize * FONT_CHAR_HEIGHT;
     }
 
     fn delay(&mut self, x: usize) {
-        self.delay = Timer::new(self.reg[x]);
+        self.delay.set(self.reg[x]);
     }
 
     fn get_delay(&mut self, x: usize) {
@@ -348,19 +1498,1201 @@ impl C8 {
     }
 
     fn sound(&mut self, x: usize) {
-        self.sound = Timer::new(self.reg[x]);
+        self.sound.set(self.reg[x]);
     }
 
+    /// Wait for a key to be pressed and released, as on the COSMAC VIP. If several keys are
+    /// pressed, the first one released wins.
+    ///
+    /// A key already down when the wait starts counts once it's released, unless the
+    /// `fx0a_fresh_press` quirk is set, in which case it has to be released and pressed again.
     fn get_key(&mut self, x: usize) {
-        if let Some((i, _)) = self.input.iter().enumerate().find(|(_, k)| **k) {
-            self.reg[x] = i as u8;
-        } else {
-            self.pc -= 2;
+        if self.waiting_for_key.is_none() {
+            self.waiting_for_key = Some(x);
+            self.waiting_keys_down = [false; 16];
+            self.waiting_keys_held = [false; 16];
+            if self.input.contains(&true) {
+                self.note_quirk("FX0A", "fx0a_fresh_press", self.quirks.fx0a_fresh_press);
+                if self.quirks.fx0a_fresh_press {
+                    self.waiting_keys_held = self.input;
+                }
+            }
+        }
+
+        for (held, pressed) in self.waiting_keys_held.iter_mut().zip(self.input) {
+            *held &= pressed;
+        }
+
+        let released = (0..16).find(|k| self.waiting_keys_down[*k] && !self.input[*k]);
+        if let Some(k) = released {
+            self.reg[x] = k as u8;
+            self.waiting_for_key = None;
+            return;
+        }
+
+        for k in 0..16 {
+            self.waiting_keys_down[k] |= self.input[k] && !self.waiting_keys_held[k];
         }
     }
 
     fn and_rand(&mut self, x: usize, val: u16) {
-        let r = random::<u8>() % 0xff;
+        let r = self.rng.gen::<u8>() % 0xff;
         self.reg[x] = r & val as u8;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    /// A machine with `program` loaded at `PROGRAM_START`
+    fn load(program: &[u8]) -> C8 {
+        let mut c8 = C8::new();
+        c8.load_bytes(program).unwrap();
+        c8
+    }
+
+    #[test]
+    fn draw_wraps_sprite_data_past_the_end_of_memory() {
+        // DXYF then a 16x16 DXY0 with I a few bytes from the end
+        let mut c8 = load(&[0xd0, 0x1f, 0xd0, 0x10]);
+        c8.quirks.draw_16x16 = true;
+        c8.i = DEFAULT_MEM_SIZE - 3;
+        c8.memory[DEFAULT_MEM_SIZE - 3..].fill(0xff);
+
+        c8.step();
+        for r in 0..3 {
+            assert_eq!(&c8.display.pixels()[r * WIDTH..r * WIDTH + 8], &[1; 8]);
+        }
+        // the rest comes from the start of memory, which is zero below the font
+        assert!(c8.display.pixels()[3 * WIDTH..].iter().all(|p| *p == 0));
+
+        c8.step();
+        assert_eq!(c8.pc, PROGRAM_START + 4);
+    }
+
+    #[test]
+    fn shifts_vx_in_place_by_default() {
+        // SHR V1, V2; SHL V3, V4
+        let mut c8 = load(&[0x81, 0x26, 0x83, 0x4e]);
+        c8.reg[1] = 0b0000_0011;
+        c8.reg[2] = 0b1000_0000;
+        c8.reg[3] = 0b1000_0001;
+        c8.reg[4] = 0b0000_0001;
+
+        c8.step();
+        assert_eq!(
+            (c8.reg[1], c8.reg[2], c8.reg[0xf]),
+            (0b0000_0001, 0b1000_0000, 1)
+        );
+        c8.step();
+        assert_eq!(
+            (c8.reg[3], c8.reg[4], c8.reg[0xf]),
+            (0b0000_0010, 0b0000_0001, 1)
+        );
+    }
+
+    #[test]
+    fn shifts_vy_into_vx_with_shift_vy() {
+        let mut c8 = load(&[0x81, 0x26, 0x83, 0x4e]);
+        c8.quirks.shift_vy = true;
+        c8.reg[1] = 0b0000_0011;
+        c8.reg[2] = 0b1000_0000;
+        c8.reg[3] = 0b1000_0001;
+        c8.reg[4] = 0b0000_0001;
+
+        c8.step();
+        assert_eq!(
+            (c8.reg[1], c8.reg[2], c8.reg[0xf]),
+            (0b0100_0000, 0b1000_0000, 0)
+        );
+        c8.step();
+        assert_eq!(
+            (c8.reg[3], c8.reg[4], c8.reg[0xf]),
+            (0b0000_0010, 0b0000_0001, 0)
+        );
+    }
+
+    #[test]
+    fn samples_sound_only_while_the_sound_timer_runs() {
+        let mut c8 = C8::new();
+        let mut samples = [0.0; 256];
+        c8.sound_active_samples(44_100, &mut samples);
+        assert!(samples.iter().all(|s| *s == 0.0));
+
+        c8.sound.set(5);
+        c8.sound_active_samples(44_100, &mut samples);
+        assert!(samples.iter().all(|s| *s != 0.0));
+        assert!(samples.iter().any(|s| *s > 0.0) && samples.iter().any(|s| *s < 0.0));
+    }
+
+    #[test]
+    fn queued_input_events_apply_at_their_cycle() {
+        // JP 0x200
+        let mut c8 = load(&[0x12, 0x00]);
+        c8.queue_input_event(5, 0x5, false);
+        c8.queue_input_event(3, 0x5, true);
+
+        for _ in 0..3 {
+            c8.step();
+        }
+        assert!(!c8.input[0x5]);
+        c8.step();
+        assert!(c8.input[0x5]);
+        c8.step();
+        assert!(c8.input[0x5]);
+        c8.step();
+        assert!(!c8.input[0x5]);
+        assert!(c8.input_events.is_empty());
+    }
+
+    #[test]
+    fn bcd_at_the_end_of_memory_wraps() {
+        // LD B, V0
+        let mut c8 = load(&[0xf0, 0x33]);
+        c8.reg[0] = 123;
+        c8.i = DEFAULT_MEM_SIZE - 1;
+
+        c8.try_step().unwrap();
+        assert_eq!(c8.memory[DEFAULT_MEM_SIZE - 1], 1);
+        assert_eq!(&c8.memory[..2], &[2, 3]);
+    }
+
+    #[test]
+    fn run_until_first_draw_stops_at_the_first_draw_or_clear() {
+        // LD V0, 1; ADD V0, 1; DRW V0, V0, 1; CLS
+        let mut c8 = load(&[0x60, 0x01, 0x70, 0x01, 0xd0, 0x01, 0x00, 0xe0]);
+        assert_eq!(c8.run_until_first_draw(100).unwrap(), 3);
+        assert_eq!(c8.pc, PROGRAM_START + 6);
+        assert_eq!(c8.run_until_first_draw(100).unwrap(), 1);
+        assert_eq!(c8.pc, PROGRAM_START + 8);
+    }
+
+    #[test]
+    fn run_until_first_draw_reports_errors() {
+        // RET with nothing on the stack
+        let mut c8 = load(&[0x00, 0xee]);
+        assert!(matches!(
+            c8.run_until_first_draw(100),
+            Err(C8Error::StackUnderflow)
+        ));
+    }
+
+    #[test]
+    fn run_for_stops_at_the_budget_or_on_halt() {
+        // JP 0x200
+        let mut c8 = load(&[0x12, 0x00]);
+        assert_eq!(c8.run_for(50).unwrap(), StepResult::BudgetExhausted);
+        assert_eq!(c8.cycles, 50);
+
+        // LD V0, 1; EXIT
+        let mut c8 = load(&[0x60, 0x01, 0x00, 0xfd]);
+        assert_eq!(c8.run_for(50).unwrap(), StepResult::Halted);
+        assert_eq!(c8.cycles, 2);
+    }
+
+    #[test]
+    fn run_until_stops_when_done() {
+        // ADD V0, 1; JP 0x200
+        let mut c8 = load(&[0x70, 0x01, 0x12, 0x00]);
+        let result = c8.run_until(1000, |c8| c8.reg[0] == 10).unwrap();
+        assert_eq!(result, StepResult::ConditionMet);
+        assert_eq!(c8.cycles, 19);
+    }
+
+    #[test]
+    fn run_for_reports_errors() {
+        let mut c8 = load(&[0xff, 0xff]);
+        assert!(matches!(
+            c8.run_for(50),
+            Err(C8Error::UnknownInstruction(0xffff))
+        ));
+    }
+
+    #[test]
+    fn timers_run_while_waiting_for_a_key() {
+        // LD V0, K
+        let mut c8 = load(&[0xf0, 0x0a]);
+        c8.delay.set(10);
+
+        for _ in 0..4 {
+            c8.step();
+            c8.decrement_timers();
+        }
+        assert!(c8.is_waiting_for_key());
+        assert_eq!(c8.pc, PROGRAM_START + 2);
+        assert_eq!(c8.delay.val(), 6);
+
+        c8.key_pressed(0x7, true);
+        c8.step();
+        c8.key_pressed(0x7, false);
+        c8.step();
+        assert!(!c8.is_waiting_for_key());
+        assert_eq!(c8.reg[0], 0x7);
+    }
+
+    #[test]
+    fn sprites_wrap_or_clip_on_each_axis() {
+        for (wrap_x, wrap_y, expected) in [
+            (false, false, "....\n....\n...#"),
+            (true, false, "....\n....\n#..#"),
+            (false, true, "...#\n....\n...#"),
+            (true, true, "#..#\n....\n#..#"),
+        ] {
+            // a 2x2 sprite at the bottom right corner: DRW V0, V1, 2
+            let mut c8 = C8::builder().display_size(4, 3).build();
+            c8.load_bytes(&[0xd0, 0x12]).unwrap();
+            c8.quirks.wrap_x = wrap_x;
+            c8.quirks.wrap_y = wrap_y;
+            c8.reg[0] = 3;
+            c8.reg[1] = 2;
+            c8.i = 0x300;
+            c8.memory[0x300..0x302].fill(0b1100_0000);
+            c8.step();
+
+            assert_eq!(
+                test_util::display_to_string(&c8).trim_end(),
+                expected,
+                "wrap_x {wrap_x}, wrap_y {wrap_y}"
+            );
+        }
+    }
+
+    #[test]
+    fn fills_memory_above_the_program() {
+        let mut c8 = C8::new();
+        c8.fill_unused_memory(0xa5);
+        c8.load_bytes(&[0x12, 0x00]).unwrap();
+        assert_eq!(&c8.memory[PROGRAM_START..PROGRAM_START + 2], &[0x12, 0x00]);
+        assert!(c8.memory[PROGRAM_START + 2..].iter().all(|b| *b == 0xa5));
+        assert_eq!(&c8.memory[FONT_START..FONT_START + FONT.len()], &FONT);
+
+        // a shorter program leaves no trace of the longer one
+        c8.load_bytes(&[0x12, 0x00, 0x00, 0xe0]).unwrap();
+        c8.load_bytes(&[0x12, 0x00]).unwrap();
+        assert!(c8.memory[PROGRAM_START + 2..].iter().all(|b| *b == 0xa5));
+    }
+
+    #[test]
+    fn fx0a_returns_the_first_key_released() {
+        // LD V0, K
+        let mut c8 = load(&[0xf0, 0x0a]);
+        c8.step();
+        c8.key_pressed(0x2, true);
+        c8.step();
+        c8.key_pressed(0x9, true);
+        c8.step();
+        c8.key_pressed(0x9, false);
+        c8.step();
+        assert!(!c8.is_waiting_for_key());
+        assert_eq!(c8.reg[0], 0x9);
+    }
+
+    #[test]
+    fn paused_timers_stay_put() {
+        let mut c8 = C8::new();
+        c8.delay.set(10);
+        c8.sound.set(10);
+        c8.pause_timers(true);
+
+        std::thread::sleep(Duration::from_millis(50));
+        c8.update_timers();
+        c8.decrement_timers();
+        assert_eq!((c8.delay.val(), c8.sound.val()), (10, 10));
+
+        // the time spent paused isn't made up for
+        c8.pause_timers(false);
+        c8.update_timers();
+        assert_eq!(c8.delay.val(), 10);
+        c8.decrement_timers();
+        assert_eq!((c8.delay.val(), c8.sound.val()), (9, 9));
+    }
+
+    #[test]
+    fn strict_mode_rejects_odd_and_out_of_range_targets() {
+        let misaligned = |program: &[u8], v0: u8| {
+            let mut c8 = load(program);
+            c8.set_strict(true);
+            c8.reg[0] = v0;
+            match c8.try_step() {
+                Err(C8Error::MisalignedJump { addr }) => Some(addr),
+                _ => None,
+            }
+        };
+        // JP 0x201; CALL 0x203; JP V0, 0xFFE
+        assert_eq!(misaligned(&[0x12, 0x01], 0), Some(0x201));
+        assert_eq!(misaligned(&[0x22, 0x03], 0), Some(0x203));
+        assert_eq!(misaligned(&[0xbf, 0xfe], 2), Some(0x1000));
+        assert_eq!(misaligned(&[0xbf, 0xfe], 0), None);
+        assert_eq!(misaligned(&[0xb3, 0x00], 2), None);
+
+        // RET to an odd address
+        let mut c8 = load(&[0x00, 0xee]);
+        c8.set_strict(true);
+        c8.stack.push_front(0x301);
+        assert!(matches!(
+            c8.try_step(),
+            Err(C8Error::MisalignedJump { addr: 0x301 })
+        ));
+    }
+
+    #[test]
+    fn odd_targets_are_followed_outside_strict_mode() {
+        let mut c8 = load(&[0x12, 0x01]);
+        c8.try_step().unwrap();
+        assert_eq!(c8.pc, 0x201);
+    }
+
+    #[test]
+    fn dxy0_draws_nothing_without_draw_16x16() {
+        // DRW V0, V0, 0
+        let mut c8 = load(&[0xd0, 0x00]);
+        c8.i = 0x300;
+        c8.memory[0x300..0x320].fill(0xff);
+        c8.step();
+        assert!(c8.display.pixels().iter().all(|p| *p == 0));
+        assert_eq!(c8.draws, 0);
+    }
+
+    #[test]
+    fn dxy0_draws_16x16_with_draw_16x16() {
+        // one row taller than the sprite, to show nothing is drawn below it
+        let mut c8 = C8::builder().display_size(16, 17).build();
+        c8.load_bytes(&[0xd0, 0x00]).unwrap();
+        c8.quirks.draw_16x16 = true;
+        c8.i = 0x300;
+        // each row is two bytes, here the left half lit on even rows and the right on odd ones
+        for r in 0..16 {
+            c8.memory[0x300 + r * 2..0x302 + r * 2].copy_from_slice(if r % 2 == 0 {
+                &[0xff, 0x00]
+            } else {
+                &[0x00, 0xff]
+            });
+        }
+        c8.step();
+        let even = format!("{}{}\n", "#".repeat(8), ".".repeat(8));
+        let odd = format!("{}{}\n", ".".repeat(8), "#".repeat(8));
+        assert_display_eq!(
+            c8,
+            &format!("{}{}", (even + &odd).repeat(8), ".".repeat(16))
+        );
+    }
+
+    #[test]
+    fn soft_reset_keeps_memory_and_rpl_flags() {
+        let mut c8 = load(&[
+            0x60, 0x05, // LD V0, 0x05
+            0x61, 0x07, // LD V1, 0x07
+            0xf1, 0x75, // LD R, V1
+            0xf0, 0x15, // LD DT, V0
+            0xa3, 0x00, // LD I, 0x300
+            0xd0, 0x01, // DRW V0, V0, 1
+            0x22, 0x10, // CALL 0x210
+            0x00, 0x00, //
+            0x12, 0x10, // JP 0x210
+        ]);
+        c8.memory[0x300] = 0xff;
+        for _ in 0..7 {
+            c8.step();
+        }
+        c8.memory[0x400] = 0x42;
+        c8.soft_reset();
+
+        assert_eq!(c8.pc, PROGRAM_START);
+        assert_eq!(c8.i, 0);
+        assert_eq!(c8.reg, [0; 16]);
+        assert!(c8.stack.is_empty());
+        assert_eq!(c8.delay.val(), 0);
+        assert!(c8.display.pixels().iter().all(|p| *p == 0));
+        assert_eq!(c8.rpl[..2], [0x05, 0x07]);
+        assert_eq!(c8.memory[0x300], 0xff);
+        assert_eq!(c8.memory[0x400], 0x42);
+        assert_eq!(c8.memory[PROGRAM_START], 0x60);
+    }
+
+    #[test]
+    fn add_immediate_never_sets_vf() {
+        let mut c8 = load(&[
+            0x6f, 0xf0, // LD VF, 0xF0
+            0x7f, 0x20, // ADD VF, 0x20
+            0x60, 0xff, // LD V0, 0xFF
+            0x70, 0x02, // ADD V0, 0x02
+        ]);
+        c8.step();
+        c8.step();
+        assert_eq!(c8.reg[0xf], 0x10);
+        c8.step();
+        c8.step();
+        assert_eq!(c8.reg[0], 0x01);
+        assert_eq!(c8.reg[0xf], 0x10);
+    }
+
+    #[test]
+    fn random_programs_never_panic() {
+        let mut rng = StdRng::seed_from_u64(0xc8);
+        for _ in 0..200 {
+            let mut program = vec![0; rng.gen_range(2..512)];
+            rng.fill(&mut program[..]);
+            let mut c8 = load(&program);
+            c8.set_seed(0);
+            for _ in 0..2_000 {
+                if c8.is_halted() || c8.try_step().is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_regressions_dont_panic() {
+        // keys past F
+        let mut c8 = C8::new();
+        c8.key_pressed(0x10, true);
+        assert_eq!(c8.input, [false; 16]);
+
+        // fetching the last byte of memory
+        c8.pc = DEFAULT_MEM_SIZE - 1;
+        assert!(matches!(c8.try_step(), Err(C8Error::PcOutOfBounds(_))));
+
+        // ADD I, VX past 16 bits, then peeking at the same address
+        let mut c8 = load(&[0xf0, 0x1e]);
+        c8.i = 0xffff;
+        c8.reg[0] = 1;
+        c8.step();
+        assert_eq!(c8.i, 0);
+        c8.pc = usize::MAX;
+        c8.peek_instruction();
+    }
+
+    #[test]
+    fn collision_tracking_counts_draws_that_set_vf() {
+        let mut c8 = load(&[
+            0xa3, 0x00, // LD I, 0x300
+            0xd0, 0x01, // DRW V0, V0, 1
+            0xd0, 0x01, // DRW V0, V0, 1
+            0xd0, 0x01, // DRW V0, V0, 1
+            0xd0, 0x01, // DRW V0, V0, 1
+        ]);
+        c8.memory[0x300] = 0x80;
+        c8.set_collision_tracking(true);
+        for _ in 0..3 {
+            c8.step();
+        }
+        // only the second draw erased a lit pixel
+        assert_eq!(c8.collision_count(), 1);
+        // cycles count from 0, so the third instruction runs on cycle 2
+        assert_eq!(c8.last_collision_cycle(), Some(2));
+
+        c8.reset_collisions();
+        assert_eq!(c8.collision_count(), 0);
+        assert_eq!(c8.last_collision_cycle(), None);
+        c8.step();
+        c8.step();
+        assert_eq!(c8.collision_count(), 1);
+
+        c8.set_collision_tracking(false);
+        assert_eq!(c8.collision_count(), 0);
+    }
+
+    #[test]
+    fn strict_mode_rejects_a_nonzero_low_nibble_on_register_skips() {
+        for opcode in [[0x50, 0x11], [0x90, 0x11]] {
+            // V0 and V1 are both 0, so 5011 skips and 9011 doesn't
+            let mut c8 = load(&opcode);
+            c8.step();
+            let expected = if opcode[0] == 0x50 { 4 } else { 2 };
+            assert_eq!(c8.pc, PROGRAM_START + expected);
+
+            let mut c8 = load(&opcode);
+            c8.set_strict(true);
+            assert!(matches!(
+                c8.try_step(),
+                Err(C8Error::UnknownInstruction(op)) if op.to_be_bytes() == opcode
+            ));
+        }
+
+        let mut c8 = load(&[0x50, 0x10]);
+        c8.set_strict(true);
+        c8.step();
+        assert_eq!(c8.pc, PROGRAM_START + 4);
+    }
+
+    #[test]
+    fn register_ranges_save_and_load_in_either_order() {
+        let mut c8 = load(&[
+            0x52, 0x42, // LD [I], V2-V4
+            0x54, 0x22, // LD [I], V4-V2
+        ]);
+        c8.reg[2..5].copy_from_slice(&[1, 2, 3]);
+        c8.i = 0x300;
+        c8.step();
+        assert_eq!(c8.memory[0x300..0x304], [1, 2, 3, 0]);
+        assert_eq!(c8.i, 0x300);
+        c8.i = 0x310;
+        c8.step();
+        assert_eq!(c8.memory[0x310..0x314], [3, 2, 1, 0]);
+
+        let mut c8 = load(&[
+            0x51, 0x33, // LD V1-V3, [I]
+            0x5c, 0xa3, // LD VC-VA, [I]
+        ]);
+        c8.memory[0x300..0x303].copy_from_slice(&[7, 8, 9]);
+        c8.i = 0x300;
+        c8.step();
+        assert_eq!(c8.reg[..5], [0, 7, 8, 9, 0]);
+        c8.step();
+        assert_eq!(c8.reg[0xa..0xd], [9, 8, 7]);
+    }
+
+    #[test]
+    fn jumps_past_the_end_of_memory_wrap() {
+        // JP V0, 0xFFE
+        let mut c8 = load(&[0xbf, 0xfe]);
+        c8.reg[0] = 0x12;
+        c8.step();
+        assert_eq!(c8.pc, 0x010);
+
+        let mut c8 = load(&[0xbf, 0xfe]);
+        c8.reg[0] = 0x12;
+        c8.set_strict(true);
+        assert!(matches!(
+            c8.try_step(),
+            Err(C8Error::MisalignedJump { addr: 0x1010 })
+        ));
+
+        // RET to an address pushed by something other than CALL
+        let mut c8 = load(&[0x00, 0xee]);
+        c8.stack.push_front(0x1202);
+        c8.step();
+        assert_eq!(c8.pc, 0x202);
+    }
+
+    #[test]
+    fn end_of_memory_modes() {
+        let at_end = |behaviour| {
+            let mut c8 = C8::new();
+            c8.set_end_of_memory(behaviour);
+            // LD V0, 0x05 in the last two bytes
+            c8.memory[DEFAULT_MEM_SIZE - 2..].copy_from_slice(&[0x60, 0x05]);
+            c8.pc = DEFAULT_MEM_SIZE - 2;
+            c8.step();
+            assert_eq!(c8.reg[0], 5);
+            c8
+        };
+
+        let mut c8 = at_end(EndOfMemory::Error);
+        assert_eq!(c8.pc, DEFAULT_MEM_SIZE);
+        assert!(matches!(
+            c8.try_step(),
+            Err(C8Error::PcOutOfBounds(DEFAULT_MEM_SIZE))
+        ));
+
+        let mut c8 = at_end(EndOfMemory::Halt);
+        c8.try_step().unwrap();
+        assert!(c8.is_halted());
+
+        let mut c8 = at_end(EndOfMemory::Wrap);
+        assert_eq!(c8.pc, 0);
+        c8.memory[..2].copy_from_slice(&[0x61, 0x07]);
+        c8.step();
+        assert_eq!((c8.reg[1], c8.pc), (7, 2));
+
+        // straddling the end, the low byte comes from address 0
+        let mut c8 = at_end(EndOfMemory::Wrap);
+        c8.pc = DEFAULT_MEM_SIZE - 1;
+        c8.memory[DEFAULT_MEM_SIZE - 1] = 0x62;
+        c8.memory[0] = 0x09;
+        c8.step();
+        assert_eq!((c8.reg[2], c8.pc), (9, 1));
+
+        // strict mode ignores the setting
+        let mut c8 = C8::new();
+        c8.set_end_of_memory(EndOfMemory::Wrap);
+        c8.set_strict(true);
+        c8.pc = DEFAULT_MEM_SIZE - 1;
+        assert!(matches!(c8.try_step(), Err(C8Error::PcOutOfBounds(_))));
+    }
+
+    #[test]
+    fn initial_registers_are_used_by_the_first_instruction() {
+        let mut regs = [0; 16];
+        regs[1] = 0x30;
+        regs[2] = 0x12;
+        let mut c8 = C8::builder().with_initial_registers(regs).build();
+        // ADD V1, V2
+        c8.load_bytes(&[0x81, 0x24]).unwrap();
+        assert_eq!(c8.reg, regs);
+        c8.step();
+        assert_eq!(c8.reg[1], 0x42);
+
+        let mut c8 = load(&[0x81, 0x24]);
+        c8.set_registers(regs);
+        c8.step();
+        assert_eq!(c8.reg[1], 0x42);
+        assert_eq!(c8.reg[2], 0x12);
+    }
+
+    #[test]
+    fn run_frame_steps_ticks_and_renders() {
+        let mut c8 = load(&[
+            0x60, 0x10, // LD V0, 0x10
+            0xf0, 0x15, // LD DT, V0
+            0xa3, 0x00, // LD I, 0x300
+            0xd1, 0x11, // DRW V1, V1, 1
+            0x71, 0x01, // ADD V1, 0x01
+            0x12, 0x08, // JP 0x208
+        ]);
+        c8.memory[0x300] = 0x80;
+        let mut frame = vec![0; WIDTH * HEIGHT];
+        c8.run_frame(4, &mut frame).unwrap();
+        assert_eq!(c8.cycles, 4);
+        assert_eq!(c8.delay.val(), 0x0f);
+        assert_eq!(frame[0], c8.palette.colors[1]);
+        assert_eq!(frame[1], c8.palette.colors[0]);
+
+        c8.run_frame(4, &mut frame).unwrap();
+        assert_eq!(c8.cycles, 8);
+        assert_eq!(c8.delay.val(), 0x0e);
+
+        // halting stops the instructions but not the timers
+        let mut c8 = load(&[0x60, 0x10, 0xf0, 0x15, 0x00, 0xfd]);
+        c8.run_frame(10, &mut frame).unwrap();
+        c8.run_frame(10, &mut frame).unwrap();
+        assert_eq!(c8.cycles, 3);
+        assert_eq!(c8.delay.val(), 0x0e);
+    }
+
+    #[test]
+    fn dxy0_without_draw_16x16_only_clears_vf() {
+        // DRW V0, V0, 0
+        let mut c8 = load(&[0xd0, 0x00]);
+        c8.reg[0xf] = 1;
+        c8.i = 0x300;
+        c8.memory[0x300..0x320].fill(0xff);
+        c8.step();
+        assert_eq!(c8.reg[0xf], 0);
+        assert_eq!(c8.draws, 0);
+
+        // with the quirk it's a real draw
+        let mut c8 = load(&[0xd0, 0x00, 0xd0, 0x00]);
+        c8.quirks.draw_16x16 = true;
+        c8.i = 0x300;
+        c8.memory[0x300..0x320].fill(0xff);
+        c8.step();
+        c8.step();
+        assert_eq!(c8.reg[0xf], 1);
+        assert_eq!(c8.draws, 2);
+    }
+
+    #[test]
+    fn register_dumps_wrap_past_the_end_of_memory() {
+        // LD [I], VF; LD VF, [I]
+        let mut c8 = load(&[0xff, 0x55, 0xff, 0x65]);
+        let regs: [u8; 16] = std::array::from_fn(|n| n as u8 + 1);
+        c8.reg = regs;
+        c8.i = DEFAULT_MEM_SIZE - 1;
+        c8.step();
+        assert_eq!(c8.memory[DEFAULT_MEM_SIZE - 1], 1);
+        assert_eq!(c8.memory[..15], regs[1..]);
+
+        c8.reg = [0; 16];
+        c8.i = DEFAULT_MEM_SIZE - 1;
+        c8.step();
+        assert_eq!(c8.reg, regs);
+    }
+
+    #[test]
+    fn release_all_keys_lets_go_of_held_keys() {
+        let mut c8 = C8::new();
+        c8.key_pressed(0x1, true);
+        c8.key_pressed(0xa, true);
+        c8.release_all_keys();
+        assert_eq!(c8.input, [false; 16]);
+        assert_eq!(c8.pending_input, [false; 16]);
+
+        // the releases are recorded, so replays let go too
+        let mut c8 = C8::new();
+        c8.start_replay(&[0x12, 0x00], 0, 10).unwrap();
+        c8.key_pressed(0x1, true);
+        c8.release_all_keys();
+        let replay = c8.replay().unwrap();
+        assert_eq!(replay.inputs.len(), 2);
+        assert!(!replay.inputs[1].pressed);
+    }
+
+    #[test]
+    fn decayed_keys_are_released() {
+        let mut c8 = C8::new();
+        c8.set_key_decay(Some(2));
+        c8.key_pressed(0x1, true);
+        c8.key_pressed(0x2, true);
+        c8.decrement_timers();
+        // pressing again restarts the count
+        c8.key_pressed(0x2, true);
+        c8.decrement_timers();
+        assert!(!c8.input[0x1]);
+        assert!(c8.input[0x2]);
+        c8.decrement_timers();
+        assert!(!c8.input[0x2]);
+    }
+
+    #[test]
+    fn for_platform_sets_quirks_and_allowed_instructions() {
+        for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            let c8 = C8::for_platform(platform);
+            assert_eq!(c8.quirks(), Quirks::for_platform(platform));
+            assert_eq!(c8.platform, Some(platform));
+            assert_eq!((c8.width(), c8.height()), (WIDTH, HEIGHT));
+        }
+        assert!(Quirks::for_platform(Platform::Chip8).shift_vy);
+        assert!(Quirks::for_platform(Platform::SuperChip).draw_16x16);
+        assert!(Quirks::for_platform(Platform::XoChip).wrap_x);
+        assert_eq!(Platform::Chip8.instructions_per_frame(), 10);
+        assert_eq!(Platform::XoChip.instructions_per_frame(), 30);
+
+        // quirks set afterwards win
+        let c8 = C8::builder()
+            .platform(Platform::Chip8)
+            .quirks(Quirks::default())
+            .build();
+        assert_eq!(c8.quirks(), Quirks::default());
+        assert_eq!(c8.platform, Some(Platform::Chip8));
+    }
+
+    #[test]
+    fn clip_tracking_counts_pixels_off_the_edge() {
+        let mut c8 = load(&[
+            0x60, 0x3c, // LD V0, 60
+            0x61, 0x1f, // LD V1, 31
+            0xa3, 0x00, // LD I, 0x300
+            0xd0, 0x12, // DRW V0, V1, 2
+        ]);
+        // 5 lit pixels per row, 2 of them at x 60 and 61 and the rest past the edge
+        c8.memory[0x300..0x302].fill(0b1100_1110);
+        c8.set_clip_tracking(true);
+        for _ in 0..4 {
+            c8.step();
+        }
+        // the first row loses 3 pixels and the second row, past the bottom, all 5
+        assert_eq!(c8.clipped_pixels(), 8);
+        c8.reset_clipped_pixels();
+        assert_eq!(c8.clipped_pixels(), 0);
+
+        // wrapping never clips
+        let mut c8 = load(&[0x60, 0x3c, 0x61, 0x1f, 0xa3, 0x00, 0xd0, 0x12]);
+        c8.memory[0x300..0x302].fill(0b1100_1110);
+        c8.quirks.wrap_x = true;
+        c8.quirks.wrap_y = true;
+        c8.set_clip_tracking(true);
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.clipped_pixels(), 0);
+    }
+
+    /// VF after running `program`, with and without the `shift_vy` quirk
+    fn flag_after(program: &[u8]) -> [u8; 2] {
+        [false, true].map(|shift_vy| {
+            let mut c8 = load(program);
+            c8.quirks.shift_vy = shift_vy;
+            for _ in 0..program.len() / 2 {
+                c8.step();
+            }
+            c8.reg[0xf]
+        })
+    }
+
+    #[test]
+    fn the_flag_wins_when_vf_is_the_destination() {
+        // LD VF, 1; SHR VF, VF
+        assert_eq!(flag_after(&[0x6f, 0x01, 0x8f, 0xf6]), [1, 1]);
+        // LD VF, 0x81; SHL VF, VF
+        assert_eq!(flag_after(&[0x6f, 0x81, 0x8f, 0xfe]), [1, 1]);
+        // LD VF, 0x02; LD V0, 0x01; SHR VF, V0
+        assert_eq!(flag_after(&[0x6f, 0x02, 0x60, 0x01, 0x8f, 0x06]), [0, 1]);
+        // LD VF, 0xFF; LD V0, 1; ADD VF, V0
+        assert_eq!(flag_after(&[0x6f, 0xff, 0x60, 0x01, 0x8f, 0x04]), [1, 1]);
+        // LD VF, 0; LD V0, 1; SUB VF, V0
+        assert_eq!(flag_after(&[0x6f, 0x00, 0x60, 0x01, 0x8f, 0x05]), [0, 0]);
+        // LD VF, 1; LD V0, 5; SUBN VF, V0
+        assert_eq!(flag_after(&[0x6f, 0x01, 0x60, 0x05, 0x8f, 0x07]), [1, 1]);
+    }
+
+    #[test]
+    fn vf_as_a_source_is_read_before_the_flag_is_written() {
+        let run = |program: &[u8], shift_vy| {
+            let mut c8 = load(program);
+            c8.quirks.shift_vy = shift_vy;
+            for _ in 0..program.len() / 2 {
+                c8.step();
+            }
+            (c8.reg[0], c8.reg[0xf])
+        };
+        // LD VF, 3; LD V0, 1; then ADD, SUB, SUBN, SHR or SHL V0, VF
+        let with = |op: u8| [0x6f, 0x03, 0x60, 0x01, 0x80, 0xf0 | op];
+        assert_eq!(run(&with(0x4), false), (4, 0));
+        assert_eq!(run(&with(0x5), false), (0xfe, 0));
+        assert_eq!(run(&with(0x7), false), (2, 1));
+        assert_eq!(run(&with(0x6), true), (1, 1));
+        assert_eq!(run(&with(0xe), true), (6, 0));
+        // without the quirk VY is ignored
+        assert_eq!(run(&with(0x6), false), (0, 1));
+    }
+
+    #[test]
+    fn the_vf_operand_hook_sees_flag_setting_instructions_only() {
+        let program = [
+            0x80, 0x14, // 0x200: ADD V0, V1
+            0x8f, 0x14, // 0x202: ADD VF, V1
+            0x8f, 0x11, // 0x204: OR VF, V1
+            0x80, 0xf6, // 0x206: SHR V0, VF, with VF ignored
+            0x80, 0xf3, // 0x208: XOR V0, VF
+            0x81, 0xf7, // 0x20A: SUBN V1, VF
+        ];
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut c8 = load(&program);
+        let hook_seen = seen.clone();
+        c8.set_vf_operand_hook(Some(Box::new(move |at| hook_seen.borrow_mut().push(at))));
+        for _ in 0..program.len() / 2 {
+            c8.step();
+        }
+        assert_eq!(*seen.borrow(), [0x202, 0x20a]);
+
+        // VY counts for shifts with the quirk
+        seen.borrow_mut().clear();
+        let mut c8 = load(&[0x80, 0xf6]);
+        c8.quirks.shift_vy = true;
+        let hook_seen = seen.clone();
+        c8.set_vf_operand_hook(Some(Box::new(move |at| hook_seen.borrow_mut().push(at))));
+        c8.step();
+        assert_eq!(*seen.borrow(), [0x200]);
+    }
+
+    #[test]
+    fn delay_spin_waits_last_a_tick_per_60th_of_a_second() {
+        let mut c8 = load(&[
+            0x60, 0x06, // 0x200: LD V0, 6
+            0xf0, 0x15, // 0x202: LD DT, V0
+            0xf1, 0x07, // 0x204: LD V1, DT
+            0x31, 0x00, // 0x206: SE V1, 0
+            0x12, 0x04, // 0x208: JP 0x204
+            0x00, 0xfd, // 0x20A: EXIT
+        ]);
+        let start = Instant::now();
+        while !c8.is_halted() {
+            c8.step();
+            c8.update_timers();
+        }
+        // 6 ticks are 100ms
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(80) && elapsed < Duration::from_millis(500),
+            "{elapsed:?}"
+        );
+    }
+
+    #[test]
+    fn fx0a_with_a_key_held_when_the_wait_starts() {
+        let held_then_released = |fresh_press| {
+            // LD V0, K
+            let mut c8 = load(&[0xf0, 0x0a]);
+            c8.quirks.fx0a_fresh_press = fresh_press;
+            c8.key_pressed(0x5, true);
+            c8.step();
+            c8.key_pressed(0x5, false);
+            c8.step();
+            c8
+        };
+
+        let c8 = held_then_released(false);
+        assert!(!c8.is_waiting_for_key());
+        assert_eq!(c8.reg[0], 0x5);
+
+        // with the quirk the key has to be pressed again
+        let mut c8 = held_then_released(true);
+        assert!(c8.is_waiting_for_key());
+        c8.key_pressed(0x5, true);
+        c8.step();
+        assert!(c8.is_waiting_for_key());
+        c8.key_pressed(0x5, false);
+        c8.step();
+        assert!(!c8.is_waiting_for_key());
+        assert_eq!(c8.reg[0], 0x5);
+    }
+
+    #[test]
+    fn the_frame_budget_counts_instructions_not_cycles() {
+        // JP 0x200
+        let mut c8 = load(&[0x12, 0x00]);
+        c8.set_cycle_costs(CycleCosts { families: [3; 16] });
+        c8.set_adaptive_frame_budget(1_000);
+        let allowed = c8.adaptive_frame_budget().unwrap();
+        assert_eq!(c8.run_for(usize::MAX).unwrap(), StepResult::BudgetExhausted);
+        assert_eq!(c8.cycles, 3 * allowed as u64);
+        // so few instructions in a second-long target means growing as fast as allowed
+        assert!(c8.adaptive_frame_budget().unwrap() > allowed);
+
+        // a smaller cycle budget runs fewer instructions
+        let allowed = c8.adaptive_frame_budget().unwrap();
+        let start = c8.cycles;
+        c8.run_for(6).unwrap();
+        assert_eq!(c8.cycles - start, 6);
+        assert!(c8.adaptive_frame_budget().unwrap() > allowed);
+    }
+
+    #[test]
+    fn zero_padding_runs_through() {
+        // NOP; NOP; LD V0, 1
+        let mut c8 = load(&[0x00, 0x00, 0x00, 0x00, 0x60, 0x01]);
+        let before = c8.save_state();
+        c8.step();
+        c8.step();
+        assert_eq!(c8.pc, PROGRAM_START + 4);
+        assert_eq!(c8.reg, before.reg);
+        c8.step();
+        assert_eq!(c8.reg[0], 1);
+        assert_eq!(disassemble(0x0000), "NOP");
+
+        // strict mode still runs it
+        let mut c8 = load(&[0x00, 0x00]);
+        c8.set_strict(true);
+        c8.try_step().unwrap();
+        assert_eq!(c8.pc, PROGRAM_START + 2);
+    }
+
+    #[test]
+    fn tick_clocks_count_update_timers_calls() {
+        let mut c8 = C8::builder().timer_clock(TimerClock::Ticks).build();
+        // LD V0, 3; LD DT, V0; LD ST, V0
+        c8.load_bytes(&[0x60, 0x03, 0xf0, 0x15, 0xf0, 0x18])
+            .unwrap();
+        for _ in 0..3 {
+            c8.step();
+        }
+        c8.update_timers();
+        c8.update_timers();
+        assert_eq!((c8.delay.val(), c8.sound.val()), (1, 1));
+
+        // loaded states keep the clock
+        let state = c8.save_state();
+        c8.load_state(&state).unwrap();
+        c8.update_timers();
+        c8.update_timers();
+        assert_eq!((c8.delay.val(), c8.sound.val()), (0, 0));
+
+        // the wall clock doesn't tick between calls this close together
+        let mut c8 = C8::new();
+        c8.delay.set(3);
+        c8.update_timers();
+        assert_eq!(c8.delay.val(), 3);
+    }
+
+    /// A machine following `policy` with an unknown opcode followed by `LD V0, 1`
+    fn with_unknown_opcode(policy: UnknownOpcodePolicy) -> C8 {
+        let mut c8 = C8::builder().on_unknown_opcode(policy).build();
+        c8.load_bytes(&[0xff, 0xff, 0x60, 0x01]).unwrap();
+        c8
+    }
+
+    #[test]
+    fn unknown_opcodes_are_errors_by_default() {
+        let mut c8 = with_unknown_opcode(UnknownOpcodePolicy::default());
+        assert!(matches!(
+            c8.try_step(),
+            Err(C8Error::UnknownInstruction(0xffff))
+        ));
+    }
+
+    #[test]
+    fn unknown_opcodes_can_be_skipped() {
+        let mut c8 = with_unknown_opcode(UnknownOpcodePolicy::Skip);
+        c8.try_step().unwrap();
+        c8.try_step().unwrap();
+        assert_eq!(c8.reg[0], 1);
+
+        // except in strict mode
+        let mut c8 = with_unknown_opcode(UnknownOpcodePolicy::Skip);
+        c8.set_strict(true);
+        assert!(c8.try_step().is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown instruction FFFF")]
+    fn unknown_opcodes_can_panic_even_from_try_step() {
+        let mut c8 = with_unknown_opcode(UnknownOpcodePolicy::Panic);
+        let _ = c8.try_step();
+    }
+
+    /// Sets V1 to 1 when key V0 is up and 0 when it's down, four instructions per pass
+    const SKP_LOOP: [u8; 8] = [
+        0x61, 0x00, // 0x200: LD V1, 0
+        0xe0, 0x9e, // 0x202: SKP V0
+        0x61, 0x01, // 0x204: LD V1, 1
+        0x12, 0x00, // 0x206: JP 0x200
+    ];
+
+    #[test]
+    fn run_frame_applies_snapshotted_input_at_the_frame_boundary() {
+        let mut c8 = load(&SKP_LOOP);
+        c8.set_input_snapshot(true);
+        let mut frame = vec![0; WIDTH * HEIGHT];
+        c8.key_pressed(0x0, true);
+        c8.run_frame(4, &mut frame).unwrap();
+        assert_eq!(c8.reg[1], 1);
+        c8.run_frame(4, &mut frame).unwrap();
+        assert_eq!(c8.reg[1], 0);
+    }
+
+    #[test]
+    fn snapshotted_keys_wait_for_the_frame_boundary() {
+        let mut c8 = load(&SKP_LOOP);
+        c8.set_input_snapshot(true);
+        c8.key_pressed(0x0, true);
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.reg[1], 1);
+
+        c8.update_timers();
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.reg[1], 0);
+
+        // turning snapshots off applies pending changes at once
+        c8.key_pressed(0x0, false);
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.reg[1], 0);
+        c8.set_input_snapshot(false);
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.reg[1], 1);
+    }
+
+    #[test]
+    fn input_sources_are_polled_at_frame_boundaries_and_win() {
+        let keys = Rc::new(RefCell::new([false; 16]));
+        let mut c8 = load(&SKP_LOOP);
+        let source_keys = keys.clone();
+        c8.set_input_source(Box::new(move || *source_keys.borrow()));
+
+        keys.borrow_mut()[0x0] = true;
+        assert!(!c8.input[0x0]);
+        c8.decrement_timers();
+        assert!(c8.input[0x0]);
+        c8.update_timers();
+        assert!(c8.input[0x0]);
+
+        // key_pressed shows until the next boundary, where the source replaces it
+        keys.borrow_mut()[0x0] = false;
+        c8.key_pressed(0x3, true);
+        assert!(c8.input[0x3]);
+        c8.update_timers();
+        assert_eq!(c8.input, [false; 16]);
+
+        // and instructions see it
+        keys.borrow_mut()[0x0] = true;
+        c8.update_timers();
+        for _ in 0..4 {
+            c8.step();
+        }
+        assert_eq!(c8.reg[1], 0);
+    }
+
+    /// Run `FN01` and `LD I, 0x300` on a one-row display, leaving two `DRW V0, V1, 1` to step
+    fn draw_through_planes(mask: u8, sprites: &[u8]) -> C8 {
+        let mut c8 = C8::builder().display_size(8, 1).build();
+        c8.load_bytes(&[0xf0 | mask, 0x01, 0xa3, 0x00, 0xd0, 0x11, 0xd0, 0x11])
+            .unwrap();
+        c8.memory[0x300..0x300 + sprites.len()].copy_from_slice(sprites);
+        c8.step();
+        c8.step();
+        c8
+    }
+
+    #[test]
+    fn plane_mask_zero_draws_nothing() {
+        let mut c8 = draw_through_planes(0, &[0xff, 0xff]);
+        c8.step();
+        assert!(c8.display.pixels().iter().all(|p| *p == 0));
+        assert_eq!(c8.reg[0xf], 0);
+    }
+
+    #[test]
+    fn plane_mask_three_xors_a_sprite_into_each_plane() {
+        let mut c8 = draw_through_planes(3, &[0b1100_0000, 0b1010_0000]);
+        c8.step();
+        assert_eq!(c8.display.pixels()[..4], [3, 1, 2, 0]);
+        assert_eq!(c8.reg[0xf], 0);
+
+        c8.step();
+        assert!(c8.display.pixels().iter().all(|p| *p == 0));
+        assert_eq!(c8.reg[0xf], 1);
+    }
+
+    #[test]
+    fn a_collision_on_plane_two_alone_sets_vf() {
+        let mut c8 = draw_through_planes(2, &[0x80]);
+        // lit on plane 1 only, which plane 2 doesn't collide with
+        *c8.display.get_mut(0, 0) = 1;
+        c8.step();
+        assert_eq!(c8.display.pixels()[0], 3);
+        assert_eq!(c8.reg[0xf], 0);
+
+        c8.step();
+        assert_eq!(c8.display.pixels()[0], 1);
+        assert_eq!(c8.reg[0xf], 1);
+    }
+
+    #[test]
+    fn clear_only_clears_the_selected_planes() {
+        for (mask, left) in [(0, 3), (1, 2), (2, 1), (3, 0)] {
+            // FN01, CLS
+            let mut c8 = load(&[0xf0 | mask, 0x01, 0x00, 0xe0]);
+            c8.display.pixels_mut().fill(3);
+            c8.step();
+            c8.step();
+            assert!(
+                c8.display.pixels().iter().all(|p| *p == left),
+                "mask {mask}"
+            );
+        }
+    }
+
+    #[test]
+    fn set_quirks_applies_from_the_next_instruction() {
+        // SHR V1, V2 twice
+        let mut c8 = load(&[0x81, 0x26, 0x81, 0x26]);
+        c8.reg[1] = 0b1000_0000;
+        c8.reg[2] = 0b0000_1000;
+
+        c8.step();
+        assert_eq!(c8.reg[1], 0b0100_0000);
+
+        c8.set_quirks(Quirks {
+            shift_vy: true,
+            ..c8.quirks()
+        });
+        assert_eq!(
+            (c8.reg[1], c8.reg[2], c8.pc),
+            (0b0100_0000, 0b0000_1000, PROGRAM_START + 2)
+        );
+        c8.step();
+        assert_eq!(c8.reg[1], 0b0000_0100);
+        assert_eq!(c8.pc, PROGRAM_START + 4);
+    }
+
+    #[test]
+    fn set_index_clamps_to_the_last_byte_of_memory() {
+        let mut c8 = C8::new();
+        c8.set_index(0x300);
+        assert_eq!(c8.index(), 0x300);
+        c8.set_index(0x1_0000);
+        assert_eq!(c8.index(), DEFAULT_MEM_SIZE - 1);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::Platform;
+
+/// An instruction the interpreter implements, as listed by `supported_opcodes`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    /// Hex digits with X and Y for registers and N for immediates, e.g. `8XY4`
+    pub pattern: &'static str,
+    /// Mnemonic in the disassembler's syntax with the operands left symbolic
+    pub mnemonic: &'static str,
+    pub description: &'static str,
+    /// Earliest platform that defines the instruction
+    pub platform: Platform,
+    /// `Quirks` fields that change what the instruction does
+    pub quirks: &'static [&'static str],
+}
+
+/// Pattern, mnemonic, description and quirks of each arm of `C8::execute`, in the same order
+const OPCODES: [(&str, &str, &str, &[&str]); 43] = [
+    ("0000", "NOP", "Do nothing, e.g. in zero padding", &[]),
+    ("00E0", "CLS", "Clear the display", &[]),
+    ("00EE", "RET", "Return from a subroutine", &[]),
+    ("00FD", "EXIT", "Halt the program", &[]),
+    ("1NNN", "JP NNN", "Jump to NNN", &[]),
+    ("2NNN", "CALL NNN", "Call the subroutine at NNN", &[]),
+    (
+        "3XNN",
+        "SE VX, NN",
+        "Skip the next instruction if VX equals NN",
+        &[],
+    ),
+    (
+        "4XNN",
+        "SNE VX, NN",
+        "Skip the next instruction if VX doesn't equal NN",
+        &[],
+    ),
+    (
+        "5XY0",
+        "SE VX, VY",
+        "Skip the next instruction if VX equals VY",
+        &[],
+    ),
+    (
+        "5XY2",
+        "LD [I], VX-VY",
+        "Store VX to VY at I, without changing I",
+        &[],
+    ),
+    (
+        "5XY3",
+        "LD VX-VY, [I]",
+        "Load VX to VY from I, without changing I",
+        &[],
+    ),
+    ("6XNN", "LD VX, NN", "Set VX to NN", &[]),
+    (
+        "7XNN",
+        "ADD VX, NN",
+        "Add NN to VX, without setting VF",
+        &[],
+    ),
+    ("8XY0", "LD VX, VY", "Set VX to VY", &[]),
+    ("8XY1", "OR VX, VY", "Set VX to VX OR VY", &[]),
+    ("8XY2", "AND VX, VY", "Set VX to VX AND VY", &[]),
+    ("8XY3", "XOR VX, VY", "Set VX to VX XOR VY", &[]),
+    (
+        "8XY4",
+        "ADD VX, VY",
+        "Add VY to VX, setting VF on carry",
+        &[],
+    ),
+    (
+        "8XY5",
+        "SUB VX, VY",
+        "Subtract VY from VX, clearing VF on borrow",
+        &[],
+    ),
+    (
+        "8XY6",
+        "SHR VX, VY",
+        "Shift right by one, putting the old low bit in VF",
+        &["shift_vy"],
+    ),
+    (
+        "8XY7",
+        "SUBN VX, VY",
+        "Set VX to VY minus VX, clearing VF on borrow",
+        &[],
+    ),
+    (
+        "8XYE",
+        "SHL VX, VY",
+        "Shift left by one, putting the old high bit in VF",
+        &["shift_vy"],
+    ),
+    (
+        "9XY0",
+        "SNE VX, VY",
+        "Skip the next instruction if VX doesn't equal VY",
+        &[],
+    ),
+    ("ANNN", "LD I, NNN", "Set I to NNN", &[]),
+    ("BNNN", "JP V0, NNN", "Jump to NNN plus V0", &[]),
+    ("CXNN", "RND VX, NN", "Set VX to a random byte AND NN", &[]),
+    (
+        "DXYN",
+        "DRW VX, VY, N",
+        "Draw an N-row sprite from I at (VX, VY), setting VF on collision",
+        &["wrap_x", "wrap_y", "draw_16x16"],
+    ),
+    (
+        "EX9E",
+        "SKP VX",
+        "Skip the next instruction if key VX is down",
+        &[],
+    ),
+    (
+        "EXA1",
+        "SKNP VX",
+        "Skip the next instruction if key VX is up",
+        &[],
+    ),
+    (
+        "FN01",
+        "PLANE N",
+        "Select the display planes to draw to",
+        &[],
+    ),
+    (
+        "F002",
+        "AUDIO",
+        "Load the 16-byte audio pattern from I",
+        &[],
+    ),
+    (
+        "FX3A",
+        "PITCH VX",
+        "Set the audio pattern's pitch to VX",
+        &[],
+    ),
+    (
+        "FX0A",
+        "LD VX, K",
+        "Wait for a key press and release, and put it in VX",
+        &["fx0a_fresh_press"],
+    ),
+    (
+        "FX33",
+        "LD B, VX",
+        "Store the decimal digits of VX at I",
+        &[],
+    ),
+    ("FX55", "LD [I], VX", "Store V0 to VX at I", &[]),
+    ("FX65", "LD VX, [I]", "Load V0 to VX from I", &[]),
+    ("FX75", "LD R, VX", "Save V0 to VX in the user flags", &[]),
+    (
+        "FX85",
+        "LD VX, R",
+        "Restore V0 to VX from the user flags",
+        &[],
+    ),
+    (
+        "FX29",
+        "LD F, VX",
+        "Point I at the font character for the low nibble of VX",
+        &[],
+    ),
+    ("FX15", "LD DT, VX", "Set the delay timer to VX", &[]),
+    ("FX18", "LD ST, VX", "Set the sound timer to VX", &[]),
+    ("FX07", "LD VX, DT", "Set VX to the delay timer", &[]),
+    ("FX1E", "ADD I, VX", "Add VX to I", &[]),
+];
+
+/// Every instruction the interpreter implements, for documentation and help screens. `DXY0` is
+/// listed under `DXYN`, since it's the same instruction with the `draw_16x16` quirk deciding
+/// what a height of 0 means.
+pub fn supported_opcodes() -> Vec<OpcodeInfo> {
+    OPCODES
+        .iter()
+        .map(|&(pattern, mnemonic, description, quirks)| OpcodeInfo {
+            pattern,
+            mnemonic,
+            description,
+            platform: Platform::for_pattern(pattern),
+            quirks,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{disassemble, error::C8Error, C8};
+
+    /// Whether `opcode` matches `pattern`, with X, Y and N matching any digit
+    fn matches(pattern: &str, opcode: u16) -> bool {
+        pattern
+            .chars()
+            .zip(format!("{opcode:04X}").chars())
+            .all(|(p, d)| matches!(p, 'X' | 'Y' | 'N') || p == d)
+    }
+
+    #[test]
+    fn opcodes_execute_and_disassemble_agree() {
+        for opcode in 0..=0xffff {
+            let listed = OPCODES.iter().any(|(pattern, ..)| matches(pattern, opcode));
+            let disassembled = !disassemble(opcode).starts_with("DW");
+
+            // strict, so that only the exact encodings are accepted
+            let mut c8 = C8::new();
+            c8.set_strict(true);
+            c8.load_bytes(&opcode.to_be_bytes()).unwrap();
+            c8.stack.push_front(0x202);
+            let executed = !matches!(c8.try_step(), Err(C8Error::UnknownInstruction(_)));
+
+            assert_eq!(
+                listed, executed,
+                "{opcode:04X}: listed {listed}, executed {executed}"
+            );
+            assert_eq!(
+                listed, disassembled,
+                "{opcode:04X} listed but not disassembled"
+            );
+        }
+    }
+}
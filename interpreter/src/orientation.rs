@@ -0,0 +1,97 @@
+/// Clockwise rotation applied by `C8::render`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+/// How the display maps onto the frame `render` writes: flipped first, then rotated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub flip_h: bool,
+    pub flip_v: bool,
+}
+
+impl Orientation {
+    /// Whether the frame is taller than it is wide compared to the display
+    pub fn transposed(&self) -> bool {
+        matches!(self.rotation, Rotation::Cw90 | Rotation::Cw270)
+    }
+
+    /// Index in the frame of pixel `i` of a `width` x `height` display
+    pub fn index(&self, i: usize, width: usize, height: usize) -> usize {
+        if *self == Orientation::default() {
+            return i;
+        }
+
+        let (mut c, mut r) = (i % width, i / width);
+        if self.flip_h {
+            c = width - 1 - c;
+        }
+        if self.flip_v {
+            r = height - 1 - r;
+        }
+        match self.rotation {
+            Rotation::None => r * width + c,
+            Rotation::Cw90 => c * height + (height - 1 - r),
+            Rotation::Cw180 => (height - 1 - r) * width + (width - 1 - c),
+            Rotation::Cw270 => (width - 1 - c) * height + r,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lay out a 3x2 display with pixels labelled "abcdef" in reading order
+    fn orient(orientation: Orientation) -> String {
+        let mut frame = [' '; 6];
+        for (i, label) in "abcdef".chars().enumerate() {
+            frame[orientation.index(i, 3, 2)] = label;
+        }
+        frame.iter().collect()
+    }
+
+    fn rotated(rotation: Rotation) -> Orientation {
+        Orientation {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rotations_turn_the_display_clockwise() {
+        assert_eq!(orient(rotated(Rotation::None)), "abcdef");
+        // frames 2 wide and 3 tall
+        assert_eq!(orient(rotated(Rotation::Cw90)), "daebfc");
+        assert_eq!(orient(rotated(Rotation::Cw180)), "fedcba");
+        assert_eq!(orient(rotated(Rotation::Cw270)), "cfbead");
+        assert!(rotated(Rotation::Cw90).transposed());
+        assert!(!rotated(Rotation::Cw180).transposed());
+    }
+
+    #[test]
+    fn flips_apply_before_rotation() {
+        let flip_h = Orientation {
+            flip_h: true,
+            ..Default::default()
+        };
+        assert_eq!(orient(flip_h), "cbafed");
+        let flip_v = Orientation {
+            flip_v: true,
+            ..Default::default()
+        };
+        assert_eq!(orient(flip_v), "defabc");
+        let flip_h_cw90 = Orientation {
+            rotation: Rotation::Cw90,
+            flip_h: true,
+            flip_v: false,
+        };
+        assert_eq!(orient(flip_h_cw90), "fcebda");
+    }
+}
@@ -0,0 +1,76 @@
+/// RGB black
+const BLACK: u32 = 0x00_00_00;
+
+/// RGB green
+const GREEN: u32 = 0x00_ff_00;
+
+/// RGB orange
+const ORANGE: u32 = 0xff_99_00;
+
+/// RGB white
+const WHITE: u32 = 0xff_ff_ff;
+
+/// Colors used by `C8::render`, indexed by the XO-CHIP planes lit at each pixel.
+///
+/// Entry 0 is the background and entry 1 the first plane, which is all classic programs use.
+/// Entry 2 is the second plane, and entry 3 pixels lit in both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [u32; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: [BLACK, GREEN, ORANGE, WHITE],
+        }
+    }
+}
+
+/// Channel order of the pixels written by `C8::render`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// `0x00RRGGBB`
+    #[default]
+    Rgb,
+    /// `0x00BBGGRR`
+    Bgr,
+}
+
+impl PixelFormat {
+    /// Pack an RGB color into this format
+    pub fn pack(self, rgb: u32) -> u32 {
+        match self {
+            PixelFormat::Rgb => rgb,
+            PixelFormat::Bgr => (rgb & 0xff) << 16 | (rgb & 0xff_00) | (rgb >> 16) & 0xff,
+        }
+    }
+}
+
+/// Mix `from` and `to` channel by channel, `num / den` of the way towards `to`
+pub(crate) fn blend(from: u32, to: u32, num: u32, den: u32) -> u32 {
+    [16, 8, 0].iter().fold(0, |rgb, shift| {
+        let a = (from >> shift) & 0xff;
+        let b = (to >> shift) & 0xff;
+        let c = (a * (den - num) + b * num) / den;
+        rgb | c << shift
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_rgb_and_bgr() {
+        let color = 0x12_34_56;
+        assert_eq!(
+            PixelFormat::Rgb.pack(color).to_le_bytes(),
+            [0x56, 0x34, 0x12, 0]
+        );
+        assert_eq!(
+            PixelFormat::Bgr.pack(color).to_le_bytes(),
+            [0x12, 0x34, 0x56, 0]
+        );
+    }
+}
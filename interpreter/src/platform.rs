@@ -0,0 +1,113 @@
+use std::fmt;
+
+/// Chip-8 variants a program may target
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        })
+    }
+}
+
+impl Platform {
+    /// Guess the platform a program targets from the opcodes it contains.
+    ///
+    /// The program is scanned linearly so sprite data can be mistaken for instructions; treat the
+    /// result as a hint.
+    pub fn detect(program: &[u8]) -> Platform {
+        program
+            .chunks_exact(2)
+            .map(|op| Platform::for_opcode(op[0], op[1]))
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Instructions per 60Hz frame programs for the platform are usually tuned for, e.g. to pass
+    /// to `C8::run_frame`
+    pub fn instructions_per_frame(self) -> usize {
+        match self {
+            Platform::Chip8 => 10,
+            Platform::SuperChip | Platform::XoChip => 30,
+        }
+    }
+
+    /// Whether a program targeting this platform may use `opcode`
+    pub fn allows(self, opcode: u16) -> bool {
+        let [hi, lo] = opcode.to_be_bytes();
+        Platform::for_opcode(hi, lo) <= self
+    }
+
+    /// The earliest platform that defines the instructions matching a pattern like `8XY4`
+    pub(crate) fn for_pattern(pattern: &str) -> Platform {
+        // N stands for any value, so use one that isn't special-cased, like `DXY0`
+        let opcode = pattern.chars().fold(0, |op, c| {
+            op << 4 | c.to_digit(16).unwrap_or(if c == 'N' { 1 } else { 0 }) as u16
+        });
+        let [hi, lo] = opcode.to_be_bytes();
+        Platform::for_opcode(hi, lo)
+    }
+
+    /// The earliest platform that defines an opcode
+    fn for_opcode(hi: u8, lo: u8) -> Platform {
+        match (hi >> 4, hi & 0x0f, lo >> 4, lo & 0x0f) {
+            (0x0, 0x0, 0xd, _) => Platform::XoChip,
+            (0x5, _, _, 0x2 | 0x3) => Platform::XoChip,
+            (0xf, 0x0, 0x0, 0x0 | 0x2) => Platform::XoChip,
+            (0xf, _, 0x0, 0x1) => Platform::XoChip,
+            (0xf, _, 0x3, 0xa) => Platform::XoChip,
+            (0x0, 0x0, 0xc, _) => Platform::SuperChip,
+            (0x0, 0x0, 0xf, 0xb..=0xf) => Platform::SuperChip,
+            (0xd, _, _, 0x0) => Platform::SuperChip,
+            (0xf, _, 0x3, 0x0) => Platform::SuperChip,
+            (0xf, _, 0x7 | 0x8, 0x5) => Platform::SuperChip,
+            _ => Platform::Chip8,
+        }
+    }
+}
+
+/// Count the instructions in a program by their high nibble
+pub fn opcode_histogram(program: &[u8]) -> [usize; 16] {
+    let mut families = [0; 16];
+    for op in program.chunks_exact(2) {
+        families[(op[0] >> 4) as usize] += 1;
+    }
+    families
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::C8Error, C8};
+
+    #[test]
+    fn platforms_reject_later_instructions() {
+        assert!(Platform::Chip8.allows(0x8124));
+        assert!(!Platform::Chip8.allows(0x00fd));
+        assert!(Platform::SuperChip.allows(0x00fd));
+        assert!(!Platform::SuperChip.allows(0xf000));
+        assert!(Platform::XoChip.allows(0xf000));
+
+        let mut c8 = C8::new();
+        c8.set_platform(Some(Platform::Chip8));
+        // EXIT
+        c8.load_bytes(&[0x00, 0xfd]).unwrap();
+        assert!(matches!(
+            c8.try_step(),
+            Err(C8Error::IllegalForPlatform {
+                opcode: 0x00fd,
+                platform: Platform::Chip8
+            })
+        ));
+        assert!(!c8.is_halted());
+    }
+}
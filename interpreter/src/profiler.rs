@@ -0,0 +1,37 @@
+use std::time::{Duration, Instant};
+
+use crate::C8;
+
+/// Time spent executing each opcode family, by high nibble
+#[derive(Default)]
+pub(crate) struct Profiler {
+    families: [(Duration, u64); 16],
+}
+
+impl Profiler {
+    pub fn record(&mut self, family: u8, start: Instant) {
+        let (time, count) = &mut self.families[family as usize & 0xf];
+        *time += start.elapsed();
+        *count += 1;
+    }
+}
+
+impl C8 {
+    /// Time how long each instruction takes to execute, for `profile_report`. This adds
+    /// overhead to every instruction, so it's off by default. Turning it on or off resets the
+    /// totals.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler = enabled.then(Profiler::default);
+    }
+
+    /// Total execution time and number of instructions run for each opcode family, by high
+    /// nibble, leaving out families that never ran
+    pub fn profile_report(&self) -> Vec<(u8, Duration, u64)> {
+        self.profiler
+            .iter()
+            .flat_map(|p| p.families.iter().enumerate())
+            .filter(|(_, (_, count))| *count > 0)
+            .map(|(family, (time, count))| (family as u8, *time, *count))
+            .collect()
+    }
+}
@@ -0,0 +1,83 @@
+#[cfg(feature = "logging")]
+use log::warn;
+
+use crate::{
+    error::{C8Error, Result},
+    C8,
+};
+
+impl C8 {
+    /// Treat `len` bytes of memory from `start` as read-only, to catch programs that overwrite
+    /// the font or their own code, e.g. `protect_region(0x50, 80)` for the font. Calls add to the
+    /// regions already protected.
+    ///
+    /// Writes by `FX33`, `FX55` and `5XY2` into a protected region fail with
+    /// `C8Error::ProtectedWrite` in strict mode. Otherwise they go ahead and log a warning.
+    pub fn protect_region(&mut self, start: usize, len: usize) {
+        self.protected.push(start..start.saturating_add(len));
+    }
+
+    /// Make all of memory writable again
+    pub fn clear_protection(&mut self) {
+        self.protected.clear();
+    }
+
+    /// Store `byte` at `addr`, which must be in bounds, checking it isn't protected or code that
+    /// has already run. Every write by the program goes through here.
+    pub(crate) fn write_memory(&mut self, addr: usize, byte: u8) -> Result<()> {
+        if self.protected.iter().any(|region| region.contains(&addr)) {
+            if self.strict {
+                return Err(C8Error::ProtectedWrite { addr });
+            }
+            warn!("write to protected address {addr:#05x}");
+        }
+        self.note_write(addr);
+        self.log_write(addr, byte);
+        self.memory[addr] = byte;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_writes_fail_in_strict_mode() {
+        // LD I, 0x300; LD [I], V1
+        let program = [0xa3, 0x00, 0xf1, 0x55];
+        let run = |strict| {
+            let mut c8 = C8::new();
+            c8.load_bytes(&program).unwrap();
+            c8.set_strict(strict);
+            c8.protect_region(0x301, 1);
+            c8.reg[..2].copy_from_slice(&[7, 8]);
+            c8.step();
+            let result = c8.try_step();
+            (c8, result)
+        };
+
+        let (c8, result) = run(true);
+        assert!(matches!(
+            result,
+            Err(C8Error::ProtectedWrite { addr: 0x301 })
+        ));
+        // bytes before the protected one are already written
+        assert_eq!(c8.memory[0x300..0x302], [7, 0]);
+
+        let (c8, result) = run(false);
+        result.unwrap();
+        assert_eq!(c8.memory[0x300..0x302], [7, 8]);
+    }
+
+    #[test]
+    fn clearing_protection_allows_writes() {
+        let mut c8 = C8::new();
+        c8.set_strict(true);
+        c8.protect_region(0x300, 0x10);
+        assert!(c8.write_memory(0x30f, 1).is_err());
+        assert!(c8.write_memory(0x310, 1).is_ok());
+        c8.clear_protection();
+        assert!(c8.write_memory(0x30f, 1).is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::C8;
+
+/// Times instructions ran under each setting of the quirk they depend on
+pub(crate) type QuirkCounts = BTreeMap<(&'static str, &'static str, bool), u64>;
+
+/// How often an instruction whose behaviour depends on a quirk ran under one setting of it, as
+/// returned by `C8::quirk_report`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QuirkUse {
+    /// Instruction pattern, e.g. `8XY6`
+    pub opcode: &'static str,
+    /// Name of the `Quirks` field
+    pub quirk: &'static str,
+    pub enabled: bool,
+    pub count: u64,
+}
+
+impl fmt::Display for QuirkUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ran {} times with {} {}",
+            self.opcode,
+            self.count,
+            self.quirk,
+            if self.enabled { "on" } else { "off" }
+        )
+    }
+}
+
+impl C8 {
+    /// Count the instructions that run where a quirk changes what they do, for `quirk_report`.
+    /// Turning tracking on or off resets the counts.
+    pub fn set_quirk_tracking(&mut self, enabled: bool) {
+        self.quirk_counts = enabled.then(QuirkCounts::new);
+    }
+
+    /// Quirk-dependent instructions seen since tracking was turned on. Draws only count towards
+    /// the wrapping quirks when the sprite crosses that edge of the display.
+    pub fn quirk_report(&self) -> Vec<QuirkUse> {
+        self.quirk_counts
+            .iter()
+            .flatten()
+            .map(|(&(opcode, quirk, enabled), &count)| QuirkUse {
+                opcode,
+                quirk,
+                enabled,
+                count,
+            })
+            .collect()
+    }
+
+    pub(crate) fn note_quirk(&mut self, opcode: &'static str, quirk: &'static str, enabled: bool) {
+        if let Some(counts) = &mut self.quirk_counts {
+            *counts.entry((opcode, quirk, enabled)).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quirks;
+
+    #[test]
+    fn counts_shifts_under_each_setting() {
+        let mut c8 = C8::new();
+        // SHR V1, V2 three times
+        c8.load_bytes(&[0x81, 0x26, 0x81, 0x26, 0x81, 0x26])
+            .unwrap();
+        c8.set_quirk_tracking(true);
+        c8.step();
+        c8.step();
+        c8.set_quirks(Quirks {
+            shift_vy: true,
+            ..c8.quirks()
+        });
+        c8.step();
+
+        let report = c8.quirk_report();
+        assert_eq!(
+            report,
+            [
+                QuirkUse {
+                    opcode: "8XY6",
+                    quirk: "shift_vy",
+                    enabled: false,
+                    count: 2,
+                },
+                QuirkUse {
+                    opcode: "8XY6",
+                    quirk: "shift_vy",
+                    enabled: true,
+                    count: 1,
+                },
+            ]
+        );
+        let lines: Vec<_> = report.iter().map(|u| format!("{u}.")).collect();
+        assert_eq!(
+            lines,
+            [
+                "8XY6 ran 2 times with shift_vy off.",
+                "8XY6 ran 1 times with shift_vy on.",
+            ]
+        );
+    }
+}
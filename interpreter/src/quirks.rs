@@ -0,0 +1,44 @@
+use crate::Platform;
+
+/// Behaviours that differ between Chip-8 interpreters
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` set VX to VY before shifting, as on the COSMAC VIP, instead of shifting VX
+    /// in place
+    pub shift_vy: bool,
+    /// Sprites wrap around the left and right edges of the display instead of being clipped
+    pub wrap_x: bool,
+    /// Sprites wrap around the top and bottom edges of the display instead of being clipped
+    pub wrap_y: bool,
+    /// `DXY0` draws a 16x16 sprite stored as two bytes per row, as on the SUPER-CHIP, instead of
+    /// drawing nothing
+    pub draw_16x16: bool,
+    /// `FX0A` ignores keys that are already down when it starts until they're released and
+    /// pressed again, instead of accepting them as soon as they're released
+    pub fx0a_fresh_press: bool,
+}
+
+impl Quirks {
+    /// The quirks programs for `platform` usually expect: the COSMAC VIP's for CHIP-8, and
+    /// Octo's for XO-CHIP
+    pub fn for_platform(platform: Platform) -> Quirks {
+        match platform {
+            Platform::Chip8 => Quirks {
+                shift_vy: true,
+                ..Default::default()
+            },
+            Platform::SuperChip => Quirks {
+                draw_16x16: true,
+                ..Default::default()
+            },
+            Platform::XoChip => Quirks {
+                wrap_x: true,
+                wrap_y: true,
+                draw_16x16: true,
+                ..Default::default()
+            },
+        }
+    }
+}
@@ -0,0 +1,563 @@
+use crate::{palette::blend, Palette, PixelFormat, Rotation, C8};
+
+/// Frames a pixel takes to fade out
+const FADE_FRAMES: u8 = 4;
+
+/// Fade state of a single pixel
+#[derive(Clone, Copy)]
+pub struct Phosphor {
+    /// Planes last lit at the pixel
+    planes: u8,
+    /// Frames since the pixel was last lit
+    age: u8,
+}
+
+impl Default for Phosphor {
+    fn default() -> Self {
+        Self {
+            planes: 0,
+            age: FADE_FRAMES,
+        }
+    }
+}
+
+impl Phosphor {
+    /// Advance a frame given the planes now lit and return the color to show
+    fn update(&mut self, planes: u8, palette: &Palette) -> u32 {
+        if planes != 0 {
+            self.planes = planes;
+            self.age = 0;
+            return palette.colors[planes as usize];
+        }
+
+        self.age = self.age.saturating_add(1).min(FADE_FRAMES);
+        blend(
+            palette.colors[self.planes as usize],
+            palette.colors[0],
+            self.age as u32,
+            FADE_FRAMES as u32,
+        )
+    }
+}
+
+/// Display contents as of the last sprite draw or clear allowed to show this frame
+pub struct DrawLimit {
+    limit: usize,
+    /// Sprites drawn and clears since the last `render`
+    drawn: usize,
+    /// Pixels shown by `render`, row-major
+    visible: Vec<u8>,
+}
+
+impl C8 {
+    pub fn render(&mut self, frame: &mut [u32]) {
+        // taken out while rendering, since working out the colors updates them
+        let (mut fade, mut previous_frame) = (self.fade.take(), self.previous_frame.take());
+        self.render_with(frame, |i, planes| {
+            let color = match fade.as_mut().map(|f| &mut f[i]) {
+                Some(phosphor) => phosphor.update(planes, &self.palette),
+                None => self.palette.colors[planes as usize],
+            };
+
+            let color = match previous_frame.as_mut().map(|f| &mut f[i]) {
+                Some(previous) => blend(std::mem::replace(previous, color), color, 1, 2),
+                None => color,
+            };
+
+            self.pixel_format.pack(color)
+        });
+        self.fade = fade;
+        self.previous_frame = previous_frame;
+
+        // anything held back this frame shows in the next one
+        if let Some(limit) = &mut self.draw_limit {
+            if limit.drawn > limit.limit {
+                limit.visible.copy_from_slice(self.display.pixels());
+            }
+            limit.drawn = 0;
+        }
+    }
+
+    /// Rotate the frame `render` writes clockwise, e.g. for a monitor mounted on its side. The
+    /// frame is `frame_width` x `frame_height`.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.orientation.rotation = rotation;
+    }
+
+    /// Mirror the frame `render` writes horizontally and/or vertically, before any rotation
+    pub fn set_flip(&mut self, horizontal: bool, vertical: bool) {
+        self.orientation.flip_h = horizontal;
+        self.orientation.flip_v = vertical;
+    }
+
+    /// Width of the frame written by `render`, which differs from the display's when rotated
+    pub fn frame_width(&self) -> usize {
+        if self.orientation.transposed() {
+            self.display.height()
+        } else {
+            self.display.width()
+        }
+    }
+
+    /// Height of the frame written by `render`, which differs from the display's when rotated
+    pub fn frame_height(&self) -> usize {
+        if self.orientation.transposed() {
+            self.display.width()
+        } else {
+            self.display.height()
+        }
+    }
+
+    /// Only show the effect of the first `limit` sprite draws and clears in each frame passed to
+    /// `render`, deferring the rest to the next frame. Collisions are still detected as each
+    /// sprite is drawn.
+    ///
+    /// This is a viewing comfort option for programs that flicker from drawing many sprites per
+    /// frame; it doesn't correspond to anything the original hardware did.
+    pub fn set_draw_limit(&mut self, limit: Option<usize>) {
+        self.draw_limit = limit.map(|limit| DrawLimit {
+            limit,
+            drawn: 0,
+            visible: self.display.pixels().to_vec(),
+        });
+    }
+
+    /// Have `render` show the display as it was at the last frame boundary, i.e. the last call to
+    /// `update_timers` or `decrement_timers`, rather than as it is now. Drawing goes to a back
+    /// buffer that's swapped in at each boundary, so a frame never shows a half-drawn scene.
+    ///
+    /// Call `update_timers` once per frame for this to work; calling it between instructions
+    /// swaps the buffers each time.
+    pub fn set_double_buffer(&mut self, enabled: bool) {
+        self.front_buffer = None;
+        if enabled {
+            self.front_buffer = Some(self.shown_pixels().to_vec());
+        }
+    }
+
+    /// Write the value `color` gives for each shown pixel's index and planes into `frame`,
+    /// rotated and flipped. Both `render` and `render_into` go through here.
+    fn render_with<T>(&self, frame: &mut [T], mut color: impl FnMut(usize, u8) -> T) {
+        let (width, height) = (self.display.width(), self.display.height());
+        let pixels = match &self.front_buffer {
+            Some(front) => front,
+            None => self.shown_pixels(),
+        };
+        for (i, planes) in pixels.iter().enumerate() {
+            if let Some(pixel) = frame.get_mut(self.orientation.index(i, width, height)) {
+                *pixel = color(i, *planes);
+            }
+        }
+    }
+
+    /// Pixels `render` would show without double buffering
+    fn shown_pixels(&self) -> &[u8] {
+        match &self.draw_limit {
+            Some(limit) => &limit.visible,
+            None => self.display.pixels(),
+        }
+    }
+
+    /// Show what's been drawn since the last frame boundary, if double buffering
+    pub(crate) fn swap_buffers(&mut self) {
+        if let Some(mut front) = self.front_buffer.take() {
+            front.copy_from_slice(self.shown_pixels());
+            self.front_buffer = Some(front);
+        }
+    }
+
+    /// Count a sprite draw or clear against the per-frame limit, if any
+    pub(crate) fn limit_draw(&mut self) {
+        if let Some(limit) = &mut self.draw_limit {
+            limit.drawn += 1;
+            if limit.drawn <= limit.limit {
+                limit.visible.copy_from_slice(self.display.pixels());
+            }
+        }
+    }
+
+    /// Show the display as it is now after it changes other than by drawing, e.g. on loading a
+    /// program, rather than waiting for the next draw, and have `render_dirty_rows` send it all
+    pub(crate) fn display_replaced(&mut self) {
+        if let Some(limit) = &mut self.draw_limit {
+            limit.visible.copy_from_slice(self.display.pixels());
+        }
+        self.dirty_rows.fill(true);
+    }
+
+    /// Render into a newly allocated `frame_width` x `frame_height` frame. This takes
+    /// `&mut self` because rendering advances the fade and draw limit.
+    pub fn render_to_vec(&mut self) -> Vec<u32> {
+        let mut frame = vec![0; self.frame_width() * self.frame_height()];
+        self.render(&mut frame);
+        frame
+    }
+
+    /// Render the display and fill `out` with the index and color of each pixel that differs
+    /// from `prev`, a frame from an earlier `render`. Pixels missing from `prev` count as changed.
+    pub fn render_delta(&mut self, prev: &[u32], out: &mut Vec<(u32, u32)>) {
+        let frame = self.render_to_vec();
+
+        out.clear();
+        out.extend(
+            frame
+                .iter()
+                .enumerate()
+                .filter(|(i, color)| prev.get(*i) != Some(color))
+                .map(|(i, color)| (i as u32, *color)),
+        );
+    }
+
+    /// Return each row of the display changed by a draw, clear, load or reset since the last
+    /// call, with its index and colors, e.g. to update a remote display. Every row is returned
+    /// the first time and after the palette or pixel format changes.
+    ///
+    /// Rows are taken straight from the display in the palette's colors, without fading,
+    /// blending, rotation or the draw limit, and this doesn't advance any of them, so it can be
+    /// called alongside `render`.
+    pub fn render_dirty_rows(&mut self) -> Vec<(usize, Vec<u32>)> {
+        let (width, height) = (self.display.width(), self.display.height());
+        if self.dirty_rows.len() != height {
+            self.dirty_rows = vec![true; height];
+        }
+
+        let rows = self
+            .display
+            .pixels()
+            .chunks(width)
+            .zip(&self.dirty_rows)
+            .enumerate()
+            .filter(|(_, (_, dirty))| **dirty)
+            .map(|(r, (row, _))| {
+                let colors = row
+                    .iter()
+                    .map(|planes| {
+                        self.pixel_format
+                            .pack(self.palette.colors[*planes as usize])
+                    })
+                    .collect();
+                (r, colors)
+            })
+            .collect();
+        self.dirty_rows.fill(false);
+        rows
+    }
+
+    /// Output the average of the current and previous frames, a cheap way of smoothing over the
+    /// flicker from XOR drawing
+    pub fn set_frame_blend(&mut self, enabled: bool) {
+        self.previous_frame =
+            enabled.then(|| vec![self.palette.colors[0]; self.display.pixels().len()]);
+    }
+
+    /// Let pixels that turn off decay to the background over a few frames rather than
+    /// disappearing at once, which hides much of the flicker from XOR drawing.
+    ///
+    /// Each call to `render` advances the fade by a frame.
+    pub fn set_fade(&mut self, enabled: bool) {
+        self.fade = enabled.then(|| vec![Phosphor::default(); self.display.pixels().len()]);
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.dirty_rows.fill(true);
+    }
+
+    /// Set the channel order `render` packs colors in, which varies between front-ends
+    pub fn set_pixel_format(&mut self, format: PixelFormat) {
+        self.pixel_format = format;
+        self.dirty_rows.fill(true);
+    }
+
+    /// Write the display into a `frame_width` x `frame_height` frame as `render` would, using
+    /// `on` for lit pixels and `off` otherwise. Fading and blending need colors, so they're
+    /// left out, and this doesn't advance the draw limit.
+    pub fn render_into<T: Copy>(&self, frame: &mut [T], on: T, off: T) {
+        self.render_with(frame, |_, planes| if planes != 0 { on } else { off });
+    }
+
+    pub fn render_ascii(&self) -> String {
+        let width = self.display.width();
+        let mut s = String::with_capacity((width + 1) * self.display.height());
+        for row in self.display.pixels().chunks(width) {
+            for planes in row {
+                s.push(if *planes != 0 { '#' } else { '.' });
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// The display as an opaque RGBA image in the palette's colors, one pixel per display pixel.
+    /// Unlike `render` this shows the display as it is now, without fading, blending, rotation
+    /// or the draw limit.
+    #[cfg(feature = "image")]
+    pub fn render_image(&self) -> image::RgbaImage {
+        let width = self.display.width();
+        image::RgbaImage::from_fn(width as u32, self.display.height() as u32, |c, r| {
+            let planes = self.display.pixels()[r as usize * width + c as usize];
+            let [_, red, green, blue] = self.palette.colors[planes as usize].to_be_bytes();
+            image::Rgba([red, green, blue, 0xff])
+        })
+    }
+
+    /// The display as an XBM bitmap with lit pixels set, for byte-for-byte comparisons
+    pub fn display_to_xbm(&self) -> String {
+        let (width, height) = (self.display.width(), self.display.height());
+        let bytes: Vec<String> = self
+            .display
+            .pixels()
+            .chunks(width)
+            .flat_map(|row| {
+                // XBM packs each row into bytes least significant bit first
+                row.chunks(8).map(|pixels| {
+                    let byte = pixels
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, planes)| **planes != 0)
+                        .fold(0u8, |byte, (n, _)| byte | 1 << n);
+                    format!("0x{byte:02x}")
+                })
+            })
+            .collect();
+
+        let mut s = format!(
+            "#define c8_width {width}\n#define c8_height {height}\nstatic unsigned char c8_bits[] = {{\n"
+        );
+        for (n, line) in bytes.chunks(12).enumerate() {
+            if n > 0 {
+                s.push_str(",\n");
+            }
+            s.push_str("   ");
+            s.push_str(&line.join(", "));
+        }
+        s.push_str(" };\n");
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WIDTH;
+
+    #[test]
+    fn render_into_matches_render() {
+        let mut c8 = C8::new();
+        c8.set_rotation(Rotation::Cw90);
+        *c8.display.get_mut(3, 1) = 1;
+
+        let mut colors = vec![0; c8.frame_width() * c8.frame_height()];
+        c8.render(&mut colors);
+        let mut lit = vec![false; colors.len()];
+        c8.render_into(&mut lit, true, false);
+
+        let on = c8.palette.colors[1];
+        assert_eq!(lit, colors.iter().map(|c| *c == on).collect::<Vec<_>>());
+        assert_eq!(lit.iter().filter(|p| **p).count(), 1);
+    }
+
+    #[test]
+    fn frame_blend_averages_the_last_two_frames() {
+        let mut c8 = C8::new();
+        c8.set_frame_blend(true);
+
+        *c8.display.get_mut(0, 0) = 1;
+        assert_eq!(c8.render_to_vec()[0], 0x00_7f_00);
+        *c8.display.get_mut(0, 0) = 0;
+        assert_eq!(c8.render_to_vec()[0], 0x00_7f_00);
+        assert_eq!(c8.render_to_vec()[0], 0x00_00_00);
+        assert!(c8.render_to_vec()[1..].iter().all(|c| *c == 0));
+    }
+
+    #[test]
+    fn draw_limit_shows_clears() {
+        // DRW V0, V0, 1; CLS
+        let mut c8 = C8::new();
+        c8.load_bytes(&[0xd0, 0x01, 0x00, 0xe0]).unwrap();
+        c8.set_draw_limit(Some(1));
+        c8.i = 0x300;
+        c8.memory[0x300] = 0x80;
+
+        c8.step();
+        assert_eq!(c8.render_to_vec()[0], c8.palette.colors[1]);
+        c8.step();
+        assert_eq!(c8.render_to_vec()[0], c8.palette.colors[0]);
+    }
+
+    #[test]
+    fn draw_limit_shows_the_display_after_loads_and_resets() {
+        let lit = |c8: &mut C8| c8.render_to_vec()[0] == c8.palette.colors[1];
+        let mut c8 = C8::new();
+        c8.set_draw_limit(Some(1));
+        let state = c8.save_state();
+
+        *c8.display.get_mut(0, 0) = 1;
+        c8.limit_draw();
+        assert!(lit(&mut c8));
+        c8.load_bytes(&[0x12, 0x00]).unwrap();
+        assert!(!lit(&mut c8));
+
+        *c8.display.get_mut(0, 0) = 1;
+        c8.limit_draw();
+        assert!(lit(&mut c8));
+        c8.soft_reset();
+        assert!(!lit(&mut c8));
+
+        *c8.display.get_mut(0, 0) = 1;
+        c8.limit_draw();
+        assert!(lit(&mut c8));
+        c8.load_state(&state).unwrap();
+        assert!(!lit(&mut c8));
+    }
+
+    #[test]
+    fn render_delta_lists_changed_pixels() {
+        let mut c8 = C8::new();
+        let prev = c8.render_to_vec();
+        *c8.display.get_mut(5, 2) = 1;
+
+        let mut delta = Vec::new();
+        c8.render_delta(&prev, &mut delta);
+        assert_eq!(delta, [(2 * WIDTH as u32 + 5, c8.palette.colors[1])]);
+    }
+
+    #[test]
+    fn render_delta_indexes_large_displays() {
+        let mut c8 = C8::builder().display_size(512, 256).build();
+        let prev = c8.render_to_vec();
+        *c8.display.get_mut(511, 255) = 1;
+
+        let mut delta = Vec::new();
+        c8.render_delta(&prev, &mut delta);
+        assert_eq!(delta, [(512 * 256 - 1, c8.palette.colors[1])]);
+    }
+
+    #[test]
+    fn xbm_packs_rows_least_significant_bit_first() {
+        let mut c8 = C8::new();
+        let pixels = c8.display.pixels_mut();
+        pixels[0] = 1;
+        pixels[9] = 1;
+        pixels[WIDTH * 32 - 1] = 1;
+
+        let xbm = c8.display_to_xbm();
+        assert!(xbm.starts_with(
+            "#define c8_width 64\n#define c8_height 32\nstatic unsigned char c8_bits[] = {\n   \
+             0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,\n"
+        ));
+        assert!(xbm.ends_with(",\n   0x00, 0x00, 0x00, 0x80 };\n"));
+        // three header lines, then 256 bytes twelve to a line
+        assert_eq!(xbm.lines().count(), 3 + 22);
+    }
+
+    #[test]
+    fn dirty_rows_are_the_rows_drawn_to() {
+        let mut c8 = C8::new();
+        // LD V0, 5; LD I, 0x300; DRW V0, V0, 1; DRW V0, V0, 1
+        c8.load_bytes(&[0x60, 0x05, 0xa3, 0x00, 0xd0, 0x01, 0xd0, 0x01])
+            .unwrap();
+        c8.memory[0x300] = 0x80;
+        assert_eq!(c8.render_dirty_rows().len(), 32);
+        assert!(c8.render_dirty_rows().is_empty());
+
+        c8.step();
+        c8.step();
+        c8.step();
+        let rows = c8.render_dirty_rows();
+        assert_eq!(rows.len(), 1);
+        let (r, colors) = &rows[0];
+        assert_eq!(*r, 5);
+        assert_eq!(colors.len(), WIDTH);
+        assert_eq!(colors[5], c8.palette.colors[1]);
+        assert_eq!(colors[4], c8.palette.colors[0]);
+
+        // rendering in between doesn't hide the change
+        c8.step();
+        c8.render_to_vec();
+        let rows = c8.render_dirty_rows();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].1[5], c8.palette.colors[0]);
+
+        c8.set_palette(Palette::default());
+        assert_eq!(c8.render_dirty_rows().len(), 32);
+    }
+
+    #[test]
+    fn dirty_rows_leave_the_fade_alone() {
+        let mut c8 = C8::new();
+        c8.set_fade(true);
+        *c8.display.get_mut(0, 0) = 1;
+        let mut frame = vec![0; WIDTH * 32];
+        c8.render(&mut frame);
+        *c8.display.get_mut(0, 0) = 0;
+        c8.render_dirty_rows();
+        c8.render_dirty_rows();
+        // one frame into the fade, as if render_dirty_rows hadn't been called
+        c8.render(&mut frame);
+        let palette = Palette::default();
+        assert_eq!(
+            frame[0],
+            blend(palette.colors[1], palette.colors[0], 1, FADE_FRAMES as u32)
+        );
+    }
+
+    #[test]
+    fn double_buffering_shows_the_last_frame_boundary() {
+        let mut c8 = C8::new();
+        // LD I, 0x300; DRW V0, V0, 1
+        c8.load_bytes(&[0xa3, 0x00, 0xd0, 0x01]).unwrap();
+        c8.memory[0x300] = 0x80;
+        c8.set_double_buffer(true);
+        c8.step();
+        c8.step();
+        let palette = Palette::default();
+        assert_eq!(c8.render_to_vec()[0], palette.colors[0]);
+
+        c8.decrement_timers();
+        assert_eq!(c8.render_to_vec()[0], palette.colors[1]);
+
+        // turning it off shows the display as it is at once
+        *c8.display.get_mut(1, 0) = 1;
+        c8.set_double_buffer(false);
+        assert_eq!(c8.render_to_vec()[1], palette.colors[1]);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn render_image_uses_the_palette_one_pixel_per_pixel() {
+        let mut c8 = C8::new();
+        *c8.display.get_mut(2, 1) = 1;
+        c8.set_rotation(Rotation::Cw90);
+        let image = c8.render_image();
+        assert_eq!(image.dimensions(), (64, 32));
+        let [_, r, g, b] = c8.palette.colors[1].to_be_bytes();
+        assert_eq!(*image.get_pixel(2, 1), image::Rgba([r, g, b, 0xff]));
+        let [_, r, g, b] = c8.palette.colors[0].to_be_bytes();
+        assert_eq!(*image.get_pixel(1, 2), image::Rgba([r, g, b, 0xff]));
+    }
+
+    #[test]
+    fn each_plane_combination_renders_in_its_own_color() {
+        let mut c8 = C8::new();
+        c8.load_bytes(&[
+            0xa3, 0x00, // LD I, 0x300
+            0xf1, 0x01, // PLANE 1
+            0xd0, 0x11, // DRW V0, V1, 1
+            0xf2, 0x01, // PLANE 2
+            0xd2, 0x11, // DRW V2, V1, 1
+            0xf3, 0x01, // PLANE 3
+            0xd4, 0x11, // DRW V4, V1, 1
+        ])
+        .unwrap();
+        c8.reg[2] = 1;
+        c8.reg[4] = 2;
+        c8.memory[0x300..0x302].fill(0x80);
+        for _ in 0..7 {
+            c8.step();
+        }
+
+        let frame = c8.render_to_vec();
+        assert_eq!(frame[..4], [1, 2, 3, 0].map(|p| c8.palette.colors[p]));
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{
+    error::{C8Error, Result},
+    Quirks, C8, DEFAULT_MEM_SIZE, FONT, FONT_START, PROGRAM_START,
+};
+
+/// A key press or release at a given cycle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputEvent {
+    pub cycle: u64,
+    pub key: usize,
+    pub pressed: bool,
+}
+
+/// Everything needed to reproduce a run: the program it was recorded against, the RNG seed,
+/// the quirks, the instructions per frame, and the timed key events
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    pub program_hash: u64,
+    pub seed: u64,
+    pub quirks: Quirks,
+    /// Instructions run per frame, which playback has to match since the timers count down
+    /// once a frame
+    pub ipf: usize,
+    pub inputs: Vec<InputEvent>,
+}
+
+/// Recording in progress
+pub struct Recording {
+    program_hash: u64,
+    seed: u64,
+    ipf: usize,
+    inputs: Vec<InputEvent>,
+}
+
+impl Recording {
+    pub fn record(&mut self, event: InputEvent) {
+        self.inputs.push(event);
+    }
+}
+
+impl C8 {
+    /// Reset the machine, load `program`, seed the RNG, and start recording key events for a
+    /// run of `ipf` instructions per frame
+    pub fn start_replay(&mut self, program: &[u8], seed: u64, ipf: usize) -> Result<()> {
+        self.reset_for_replay(program)?;
+        self.set_seed(seed);
+        self.recording = Some(Recording {
+            program_hash: program_hash(program),
+            seed,
+            ipf,
+            inputs: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// The replay recorded since `start_replay`, if any
+    pub fn replay(&self) -> Option<Replay> {
+        self.recording.as_ref().map(|r| Replay {
+            program_hash: r.program_hash,
+            seed: r.seed,
+            quirks: self.quirks,
+            ipf: r.ipf,
+            inputs: r.inputs.clone(),
+        })
+    }
+
+    /// Reset the machine, load `program`, and set it up to play back `replay`. The front-end
+    /// has to run `replay.ipf` instructions per frame for it to play back as recorded.
+    pub fn apply_replay(&mut self, program: &[u8], replay: &Replay) -> Result<()> {
+        if program_hash(program) != replay.program_hash {
+            return Err(C8Error::ReplayMismatch);
+        }
+
+        self.reset_for_replay(program)?;
+        self.recording = None;
+        self.set_seed(replay.seed);
+        self.quirks = replay.quirks;
+        for event in &replay.inputs {
+            self.queue_input_event(event.cycle, event.key, event.pressed);
+        }
+        Ok(())
+    }
+
+    /// Put the machine back in the state it was created in, apart from its settings, and load
+    /// `program`, so a replay starts the same way it was recorded
+    fn reset_for_replay(&mut self, program: &[u8]) -> Result<()> {
+        self.soft_reset();
+        self.rpl = [0; 16];
+        self.memory = [0; DEFAULT_MEM_SIZE];
+        self.memory[FONT_START..FONT_START + FONT.len()].copy_from_slice(&FONT);
+        self.program_end = PROGRAM_START;
+        self.cycles = 0;
+        self.input_events.clear();
+        self.input = [false; 16];
+        self.pending_input = [false; 16];
+        self.load_bytes(program)
+    }
+}
+
+/// FNV-1a hash of a program
+fn program_hash(program: &[u8]) -> u64 {
+    program.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RND V0, 0xFF; SKP V1; JP 0x200; ADD V2, 1; LD V3, DT; JP 0x200
+    const PROGRAM: [u8; 12] = [
+        0xc0, 0xff, 0xe1, 0x9e, 0x12, 0x00, 0x72, 0x01, 0xf3, 0x07, 0x12, 0x00,
+    ];
+
+    /// Run 30 frames of `ipf` instructions, holding key 0 down from frame 5 to 10
+    fn play(c8: &mut C8, ipf: usize, press: bool) {
+        let mut frame = vec![0; c8.frame_width() * c8.frame_height()];
+        for n in 0..30 {
+            if press && n == 5 {
+                c8.key_pressed(0x0, true);
+            }
+            if press && n == 10 {
+                c8.key_pressed(0x0, false);
+            }
+            c8.run_frame(ipf, &mut frame).unwrap();
+        }
+    }
+
+    #[test]
+    fn replays_reproduce_the_recorded_run() {
+        let mut c8 = C8::new();
+        c8.start_replay(&PROGRAM, 42, 7).unwrap();
+        c8.delay.set(200);
+        play(&mut c8, 7, true);
+        let recorded = c8.save_state();
+        let replay = c8.replay().unwrap();
+        assert_eq!(replay.ipf, 7);
+        assert_eq!(replay.inputs.len(), 2);
+        assert!(recorded.reg[2] > 0);
+
+        // replaying over the machine that recorded it starts from scratch all the same
+        c8.apply_replay(&PROGRAM, &replay).unwrap();
+        c8.delay.set(200);
+        play(&mut c8, replay.ipf, false);
+        assert_eq!(c8.save_state(), recorded);
+    }
+
+    #[test]
+    fn replays_must_match_the_program() {
+        let mut c8 = C8::new();
+        c8.start_replay(&PROGRAM, 42, 7).unwrap();
+        let replay = c8.replay().unwrap();
+        assert!(matches!(
+            c8.apply_replay(&[0x12, 0x00], &replay),
+            Err(C8Error::ReplayMismatch)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn replays_round_trip_through_json() {
+        let mut c8 = C8::new();
+        c8.start_replay(&PROGRAM, 42, 7).unwrap();
+        play(&mut c8, 7, true);
+        let replay = c8.replay().unwrap();
+
+        let json = serde_json::to_string(&replay).unwrap();
+        assert_eq!(serde_json::from_str::<Replay>(&json).unwrap(), replay);
+    }
+}
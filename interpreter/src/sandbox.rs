@@ -0,0 +1,104 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{
+    error::{C8Error, Result},
+    State, C8,
+};
+
+/// How a sandboxed run ended
+#[derive(Debug)]
+pub enum SandboxStatus {
+    Halted,
+    BudgetExhausted,
+    Error(C8Error),
+}
+
+/// Result of `C8::run_sandboxed`
+#[derive(Debug)]
+pub struct SandboxOutcome {
+    pub status: SandboxStatus,
+    /// Instructions executed
+    pub cycles: u64,
+    /// Machine state, including the display, when the run ended
+    pub state: State,
+}
+
+impl C8 {
+    /// Load and run an untrusted program for at most `budget` instructions.
+    ///
+    /// Execution errors are reported in the outcome rather than returned, and any panic is
+    /// caught and reported the same way, so this never panics whatever the program does. Only
+    /// a program that can't be loaded at all is an `Err`. The program runs in strict mode, so
+    /// stray jumps are errors too.
+    pub fn run_sandboxed(program: &[u8], budget: usize) -> Result<SandboxOutcome> {
+        let mut c8 = C8::new();
+        c8.set_strict(true);
+        c8.load_bytes(program)?;
+
+        let status = panic::catch_unwind(AssertUnwindSafe(|| {
+            for _ in 0..budget {
+                if c8.halted {
+                    return SandboxStatus::Halted;
+                }
+                if let Err(e) = c8.try_step() {
+                    return SandboxStatus::Error(e);
+                }
+            }
+
+            if c8.halted {
+                SandboxStatus::Halted
+            } else {
+                SandboxStatus::BudgetExhausted
+            }
+        }))
+        .unwrap_or_else(|e| {
+            let msg = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            SandboxStatus::Error(C8Error::Panic(msg))
+        });
+
+        Ok(SandboxOutcome {
+            status,
+            cycles: c8.cycles,
+            state: c8.save_state(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_how_the_run_ended() {
+        // LD V0, 0x01; EXIT
+        let outcome = C8::run_sandboxed(&[0x60, 0x01, 0x00, 0xfd], 10).unwrap();
+        assert!(matches!(outcome.status, SandboxStatus::Halted));
+        assert_eq!(outcome.cycles, 2);
+        assert_eq!(outcome.state.reg[0], 1);
+
+        // JP 0x200
+        let outcome = C8::run_sandboxed(&[0x12, 0x00], 10).unwrap();
+        assert!(matches!(outcome.status, SandboxStatus::BudgetExhausted));
+        assert_eq!(outcome.cycles, 10);
+
+        let outcome = C8::run_sandboxed(&[0xff, 0xff], 10).unwrap();
+        assert!(matches!(
+            outcome.status,
+            SandboxStatus::Error(C8Error::UnknownInstruction(0xffff))
+        ));
+    }
+
+    #[test]
+    fn runs_in_strict_mode() {
+        // JP 0x201
+        let outcome = C8::run_sandboxed(&[0x12, 0x01], 10).unwrap();
+        assert!(matches!(
+            outcome.status,
+            SandboxStatus::Error(C8Error::MisalignedJump { addr: 0x201 })
+        ));
+    }
+}
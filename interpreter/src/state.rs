@@ -0,0 +1,245 @@
+use crate::{
+    error::{C8Error, Result},
+    timer::Timer,
+    InputEvent, C8, DEFAULT_MEM_SIZE,
+};
+
+#[cfg(feature = "serde")]
+fn default_pitch() -> u8 {
+    crate::beep::DEFAULT_PITCH
+}
+
+/// Snapshot of the machine state, as captured by `C8::save_state`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct State {
+    pub pc: usize,
+    pub i: usize,
+    pub reg: [u8; 16],
+    /// SUPER-CHIP user flags
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rpl: [u8; 16],
+    /// Return addresses, outermost call first
+    pub stack: Vec<usize>,
+    pub delay: u8,
+    pub sound: u8,
+    pub planes: u8,
+    pub halted: bool,
+    /// Register waiting on `FX0A` for a key press
+    pub waiting_for_key: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(with = "hex"))]
+    pub memory: Vec<u8>,
+    /// Display rows, each pixel holding the bitmask of the planes lit there
+    #[cfg_attr(feature = "serde", serde(with = "rows"))]
+    pub display: Vec<Vec<u8>>,
+    /// Cycles executed so far
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cycles: u64,
+    /// Scripted key events that haven't fired yet, ordered by cycle
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub input_events: Vec<InputEvent>,
+    /// XO-CHIP audio pattern loaded by `F002`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub audio_pattern: Option<[u8; 16]>,
+    /// XO-CHIP pitch register
+    #[cfg_attr(feature = "serde", serde(default = "default_pitch"))]
+    pub pitch: u8,
+}
+
+impl C8 {
+    pub fn save_state(&self) -> State {
+        State {
+            pc: self.pc,
+            i: self.i,
+            reg: self.reg,
+            rpl: self.rpl,
+            stack: self.stack.iter().rev().copied().collect(),
+            delay: self.delay.val(),
+            sound: self.sound.val(),
+            planes: self.planes,
+            halted: self.halted,
+            waiting_for_key: self.waiting_for_key,
+            memory: self.memory.to_vec(),
+            display: self
+                .display
+                .pixels()
+                .chunks(self.display.width())
+                .map(<[u8]>::to_vec)
+                .collect(),
+            cycles: self.cycles,
+            input_events: self.input_events.iter().copied().collect(),
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+        }
+    }
+
+    pub fn load_state(&mut self, state: &State) -> Result<()> {
+        if state.memory.len() != DEFAULT_MEM_SIZE {
+            return Err(C8Error::InvalidState(format!(
+                "memory is {} bytes, expected {DEFAULT_MEM_SIZE}",
+                state.memory.len()
+            )));
+        }
+        let (width, height) = (self.display.width(), self.display.height());
+        if state.display.len() != height || state.display.iter().any(|row| row.len() != width) {
+            return Err(C8Error::InvalidState(format!(
+                "display must be {width}x{height}"
+            )));
+        }
+
+        self.pc = state.pc;
+        self.i = state.i & 0xffff;
+        self.reg = state.reg;
+        self.rpl = state.rpl;
+        self.stack = state.stack.iter().rev().copied().collect();
+        self.delay = Timer::with_clock(state.delay, self.timer_clock);
+        self.sound = Timer::with_clock(state.sound, self.timer_clock);
+        self.planes = state.planes & 0b11;
+        self.halted = state.halted;
+        self.waiting_for_key = state.waiting_for_key.map(|x| x & 0xf);
+        self.memory.copy_from_slice(&state.memory);
+        for (r, row) in state.display.iter().enumerate() {
+            for (c, pixel) in row.iter().enumerate() {
+                *self.display.get_mut(c, r) = pixel & 0b11;
+            }
+        }
+        self.display_replaced();
+        self.cycles = state.cycles;
+        // events are scheduled by absolute cycle, so they fire where they would have in the
+        // original run
+        self.input_events = state.input_events.iter().copied().collect();
+        self.input_events.make_contiguous().sort_by_key(|e| e.cycle);
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+
+        Ok(())
+    }
+
+    /// Serialize the machine state as human-readable JSON
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.save_state()).expect("State is always serializable")
+    }
+
+    /// Create a machine from JSON produced by `to_json`
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let state: State = serde_json::from_str(json)?;
+        let mut c8 = C8::new();
+        c8.load_state(&state)?;
+        Ok(c8)
+    }
+}
+
+/// Bytes as a single hex string
+#[cfg(feature = "serde")]
+mod hex {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(d)?;
+        if !hex.is_ascii() {
+            return Err(D::Error::custom("hex string has non-ASCII characters"));
+        }
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("hex string has an odd length"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|n| u8::from_str_radix(&hex[n..n + 2], 16).map_err(D::Error::custom))
+            .collect()
+    }
+}
+
+/// Display rows as strings with one digit per pixel
+#[cfg(feature = "serde")]
+mod rows {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(rows: &[Vec<u8>], s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(rows.iter().map(|row| {
+            row.iter()
+                .map(|p| char::from_digit(*p as u32, 4).unwrap_or('?'))
+                .collect::<String>()
+        }))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Vec<u8>>, D::Error> {
+        Vec::<String>::deserialize(d)?
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| {
+                        c.to_digit(4)
+                            .map(|p| p as u8)
+                            .ok_or_else(|| D::Error::custom(format!("invalid pixel `{c}`")))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loaded_states_resume_at_the_same_cycle() {
+        // ADD V0, 1; JP 0x200
+        let mut c8 = C8::new();
+        c8.load_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        c8.queue_input_event(30, 0x4, true);
+        for _ in 0..20 {
+            c8.step();
+        }
+        let state = c8.save_state();
+
+        let mut copy = C8::new();
+        copy.load_state(&state).unwrap();
+        assert_eq!(copy.cycles(), 20);
+        for _ in 0..10 {
+            copy.step();
+        }
+        assert!(!copy.input[0x4]);
+        copy.step();
+        assert!(copy.input[0x4]);
+        assert_eq!(copy.cycles(), 31);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trips() {
+        // LD V0, 5; LD F, V0; DRW V0, V0, 5; CALL 0x200
+        let mut c8 = C8::new();
+        c8.load_bytes(&[0x60, 0x05, 0xf0, 0x29, 0xd0, 0x05, 0x22, 0x00])
+            .unwrap();
+        for _ in 0..4 {
+            c8.step();
+        }
+        c8.delay.set(7);
+        c8.queue_input_event(100, 0x3, true);
+
+        let json = c8.to_json();
+        let copy = C8::from_json(&json).unwrap();
+        assert_eq!(copy.save_state(), c8.save_state());
+        assert_eq!(copy.to_json(), json);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn from_json_rejects_non_ascii_memory() {
+        let mut state: serde_json::Value = serde_json::from_str(&C8::new().to_json()).unwrap();
+        // the right length in bytes, but not on character boundaries
+        state["memory"] = "é".repeat(DEFAULT_MEM_SIZE).into();
+        assert!(matches!(
+            C8::from_json(&state.to_string()),
+            Err(C8Error::InvalidJson(_))
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{disasm::disassemble, error::Result, C8};
+
+/// A change made by an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delta {
+    Register {
+        x: usize,
+        old: u8,
+        new: u8,
+    },
+    Index {
+        old: usize,
+        new: usize,
+    },
+    Memory {
+        addr: usize,
+        old: u8,
+        new: u8,
+    },
+    /// Number of pixels that changed
+    Display {
+        pixels: usize,
+    },
+}
+
+/// What an instruction did, as returned by `C8::step_with_info`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepInfo {
+    /// Address the instruction was fetched from
+    pub pc: usize,
+    pub opcode: u16,
+    pub mnemonic: String,
+    pub deltas: Vec<Delta>,
+}
+
+impl C8 {
+    /// The opcode at `pc` and its mnemonic, without executing it
+    pub fn peek_instruction(&self) -> (u16, String) {
+        let len = self.memory.len();
+        let opcode = u16::from_be_bytes([
+            self.memory[self.pc % len],
+            self.memory[self.pc.wrapping_add(1) % len],
+        ]);
+        (opcode, disassemble(opcode))
+    }
+
+    /// Like `try_step`, but describe the instruction and the changes it made.
+    ///
+    /// This snapshots the machine before executing, so it's much slower than `step`.
+    pub fn step_with_info(&mut self) -> Result<StepInfo> {
+        let pc = self.pc;
+        let (opcode, mnemonic) = self.peek_instruction();
+        let reg = self.reg;
+        let i = self.i;
+        let memory = self.memory;
+        let display = self.display.clone();
+
+        self.try_step()?;
+
+        let mut deltas = Vec::new();
+        for (x, (old, new)) in reg.iter().zip(self.reg.iter()).enumerate() {
+            if old != new {
+                deltas.push(Delta::Register {
+                    x,
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+        if i != self.i {
+            deltas.push(Delta::Index {
+                old: i,
+                new: self.i,
+            });
+        }
+        for (addr, (old, new)) in memory.iter().zip(self.memory.iter()).enumerate() {
+            if old != new {
+                deltas.push(Delta::Memory {
+                    addr,
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+        let pixels = display
+            .pixels()
+            .iter()
+            .zip(self.display.pixels())
+            .filter(|(old, new)| old != new)
+            .count();
+        if pixels > 0 {
+            deltas.push(Delta::Display { pixels });
+        }
+
+        Ok(StepInfo {
+            pc,
+            opcode,
+            mnemonic,
+            deltas,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PROGRAM_START;
+
+    #[test]
+    fn peeking_leaves_the_machine_alone() {
+        let mut c8 = C8::new();
+        c8.load_bytes(&[0x6a, 0x42]).unwrap();
+        assert_eq!(c8.peek_instruction(), (0x6a42, "LD VA, 0x42".to_string()));
+        assert_eq!(c8.peek_instruction().0, 0x6a42);
+        assert_eq!(c8.pc, PROGRAM_START);
+        assert_eq!(c8.reg[0xa], 0);
+    }
+
+    #[test]
+    fn step_with_info_reports_changes_and_errors() {
+        let mut c8 = C8::new();
+        // LD V1, 0x05; 0xFFFF
+        c8.load_bytes(&[0x61, 0x05, 0xff, 0xff]).unwrap();
+        let info = c8.step_with_info().unwrap();
+        assert_eq!(info.pc, PROGRAM_START);
+        assert_eq!(info.mnemonic, "LD V1, 0x05");
+        assert_eq!(
+            info.deltas,
+            [Delta::Register {
+                x: 1,
+                old: 0,
+                new: 5
+            }]
+        );
+        assert!(c8.step_with_info().is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::C8;
+
+/// Render the display as ASCII, one line per row
+pub fn display_to_string(c8: &C8) -> String {
+    c8.render_ascii()
+}
+
+/// Compare the display against `expected`, ignoring trailing whitespace on each line and
+/// trailing blank lines. Panics with a line-by-line diff on mismatch.
+#[track_caller]
+pub fn assert_display_eq(c8: &C8, expected: &str) {
+    let actual = display_to_string(c8);
+    let actual = normalize(&actual);
+    let expected = normalize(expected);
+
+    if actual != expected {
+        panic!("display mismatch:\n{}", diff(&actual, &expected));
+    }
+}
+
+fn normalize(s: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = s.lines().map(str::trim_end).collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn diff(actual: &[&str], expected: &[&str]) -> String {
+    let mut out = String::new();
+    for r in 0..actual.len().max(expected.len()) {
+        let a = actual.get(r).copied().unwrap_or("");
+        let e = expected.get(r).copied().unwrap_or("");
+        if a == e {
+            out.push_str(&format!("   {r:2} {a}\n"));
+        } else {
+            out.push_str(&format!("-  {r:2} {e}\n"));
+            out.push_str(&format!("+  {r:2} {a}\n"));
+        }
+    }
+    out
+}
+
+/// Assert the display of a `C8` matches the expected ASCII rendering
+#[macro_export]
+macro_rules! assert_display_eq {
+    ($c8:expr, $expected:expr) => {
+        $crate::test_util::assert_display_eq(&$c8, $expected)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assert_display_eq_ignores_trailing_whitespace() {
+        let mut c8 = C8::builder().display_size(3, 2).build();
+        *c8.display.get_mut(1, 0) = 1;
+        assert_eq!(display_to_string(&c8), ".#.\n...\n");
+        assert_display_eq!(c8, ".#.  \n...\t\n\n\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "display mismatch:\n-   0 #..\n+   0 .#.\n    1 ...\n")]
+    fn assert_display_eq_panics_with_a_diff() {
+        let mut c8 = C8::builder().display_size(3, 2).build();
+        *c8.display.get_mut(1, 0) = 1;
+        assert_display_eq!(c8, "#..\n...");
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Furthest the throttle lets execution fall behind schedule before it stops trying to catch up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Caps the rate instructions are executed at by sleeping between them
+pub struct Throttle {
+    ips: u32,
+    start: Instant,
+    count: u32,
+}
+
+impl Throttle {
+    pub fn new(ips: u32) -> Self {
+        Self {
+            ips: ips.max(1),
+            start: Instant::now(),
+            count: 0,
+        }
+    }
+
+    /// Record an executed instruction, sleeping if it ran ahead of schedule
+    pub fn wait(&mut self) {
+        self.count += 1;
+        let due = self.start + Duration::from_secs(1) * self.count / self.ips;
+        let now = Instant::now();
+
+        if now < due {
+            thread::sleep(due - now);
+        } else if now - due > MAX_LAG {
+            self.start = now;
+            self.count = 0;
+            return;
+        }
+
+        // rebase once a second so the count stays small
+        if self.count >= self.ips {
+            self.start = due;
+            self.count = 0;
+        }
+    }
+}
@@ -1,7 +1,19 @@
 use std::time::{Duration, Instant};
 
+/// What counts the delay and sound timers down in `C8::update_timers`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimerClock {
+    /// 60 ticks per second of wall-clock time
+    #[default]
+    WallClock,
+    /// One tick per call, as if exactly a frame had passed, for tests that advance time
+    /// precisely without sleeping
+    Ticks,
+}
+
 pub struct Timer {
     val: u8,
+    clock: TimerClock,
     wait: Duration,
     acc: Duration,
     last: Instant,
@@ -12,10 +24,11 @@ pub struct Freq {
     duration: Duration,
 }
 
+/// The delay and sound timers count down at 60Hz however many instructions run in between
 impl Default for Freq {
     fn default() -> Self {
         Self {
-            ticks: 1,
+            ticks: 60,
             duration: Duration::from_secs(1),
         }
     }
@@ -30,9 +43,17 @@ impl Timer {
         Timer::with_freq(val, Default::default())
     }
 
+    pub fn with_clock(val: u8, clock: TimerClock) -> Self {
+        Self {
+            clock,
+            ..Timer::new(val)
+        }
+    }
+
     pub fn with_freq(val: u8, freq: Freq) -> Self {
         Self {
             val,
+            clock: TimerClock::WallClock,
             wait: freq.duration / freq.ticks,
             acc: Duration::ZERO,
             last: Instant::now(),
@@ -40,14 +61,84 @@ impl Timer {
     }
 
     pub fn update(&mut self) {
-        self.acc += Instant::now().duration_since(self.last);
+        if self.clock == TimerClock::Ticks {
+            self.decrement();
+            return;
+        }
+
+        let now = Instant::now();
+        self.acc += now.duration_since(self.last);
+        self.last = now;
         while self.val > 0 && self.acc >= self.wait {
             self.val = self.val.saturating_sub(1);
             self.acc -= self.wait;
         }
+
+        // keep only the progress towards the next tick while idle so a later `set` starts in
+        // phase rather than counting down a backlog at once
+        if self.val == 0 {
+            self.acc = Duration::from_nanos((self.acc.as_nanos() % self.wait.as_nanos()) as u64);
+        }
+    }
+
+    /// Discard the time elapsed since the last update
+    pub fn resume(&mut self) {
+        self.last = Instant::now();
+    }
+
+    /// Change the value without disturbing the progress towards the next tick
+    pub fn set(&mut self, val: u8) {
+        self.val = val;
+    }
+
+    pub fn decrement(&mut self) {
+        self.val = self.val.saturating_sub(1);
+    }
+
+    /// Progress towards the next tick as of the last `update`, from 0 up to but not including 1
+    pub fn fraction(&self) -> f32 {
+        (self.acc.as_secs_f32() / self.wait.as_secs_f32()).clamp(0.0, 1.0 - f32::EPSILON)
     }
 
     pub fn val(&self) -> u8 {
         self.val
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn setting_the_timer_keeps_its_progress() {
+        // like a program copying the timer out and writing it straight back, over and over
+        let mut timer = Timer::new(100);
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_millis(100) {
+            timer.update();
+            timer.set(timer.val());
+            thread::sleep(Duration::from_millis(1));
+        }
+        timer.update();
+
+        let expected = (start.elapsed().as_secs_f64() * 60.0) as i32;
+        let ticks = 100 - timer.val() as i32;
+        assert!(
+            (ticks - expected).abs() <= 1,
+            "{ticks} ticks, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn fraction_is_the_progress_towards_the_next_tick() {
+        let mut timer = Timer::new(5);
+        assert_eq!(timer.fraction(), 0.0);
+        timer.acc = timer.wait / 4;
+        assert!((timer.fraction() - 0.25).abs() < 1e-6);
+        // a backlog not yet counted down never reads as a whole tick
+        timer.acc = timer.wait * 3;
+        assert!(timer.fraction() < 1.0);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{C8, DEFAULT_MEM_SIZE};
+
+/// A write to memory by the program, as returned by `C8::take_memory_write_log`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub addr: usize,
+    pub old: u8,
+    pub new: u8,
+    /// Address of the instruction that wrote it
+    pub pc: usize,
+}
+
+impl C8 {
+    /// Record every write the program makes to memory, for `take_memory_write_log`. This can
+    /// grow quickly, so it's off by default. Turning it off discards the log.
+    pub fn enable_memory_write_log(&mut self, enabled: bool) {
+        self.write_log = enabled.then(Vec::new);
+    }
+
+    /// The writes recorded since the last call, oldest first
+    pub fn take_memory_write_log(&mut self) -> Vec<MemoryWrite> {
+        self.write_log
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Record a write of `new` to `addr`, before it happens
+    pub(crate) fn log_write(&mut self, addr: usize, new: u8) {
+        if let Some(log) = &mut self.write_log {
+            log.push(MemoryWrite {
+                addr,
+                old: self.memory[addr],
+                new,
+                pc: self.pc.wrapping_sub(2) % DEFAULT_MEM_SIZE,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_each_write_with_the_instruction_that_made_it() {
+        let mut c8 = C8::new();
+        // LD V0, 123; LD I, 0x300; LD B, V0; LD B, V0
+        c8.load_bytes(&[0x60, 0x7b, 0xa3, 0x00, 0xf0, 0x33, 0xf0, 0x33])
+            .unwrap();
+        c8.step();
+        c8.step();
+        // not logged before it's enabled
+        c8.step();
+        assert!(c8.take_memory_write_log().is_empty());
+
+        c8.enable_memory_write_log(true);
+        c8.memory[0x301] = 0x09;
+        c8.step();
+        let write = |addr, old, new| MemoryWrite {
+            addr,
+            old,
+            new,
+            pc: 0x206,
+        };
+        assert_eq!(
+            c8.take_memory_write_log(),
+            [write(0x300, 1, 1), write(0x301, 9, 2), write(0x302, 3, 3)]
+        );
+        assert!(c8.take_memory_write_log().is_empty());
+    }
+}